@@ -0,0 +1,415 @@
+#[cfg(feature = "conv")]
+mod palette_file;
+mod palette_index;
+mod quantize;
+
+use itertools::Itertools;
+#[cfg(feature = "conv")]
+pub use palette_file::{PaletteFormat, read_palette, write_palette};
+pub use palette_index::{ColorDistance, PaletteIndex};
+pub use quantize::median_cut;
+
+use super::{Parser, gamma_table::Gamma, texture::RgbaBuffer};
+use crate::{error, utils::nom::*};
+
+const COLORS_COUNT: usize = 256;
+const SHADES_COUNT: usize = 32;
+
+// TODO(nenikitov): Potentially move to a separate module
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub fn from_12_bit(color: u16) -> Self {
+        // TODO(nenikitov): return result.
+        assert!(color <= 0xFFF, "12 bit color is smaller than 0xFFF");
+
+        let r = (color & 0xF00) >> 8;
+        let g = (color & 0x0F0) >> 4;
+        let b = color & 0x00F;
+
+        let r = (r | r << 4) as u8;
+        let g = (g | g << 4) as u8;
+        let b = (b | b << 4) as u8;
+
+        Color { r, g, b }
+    }
+
+    /// Packs the color into the 12-bit `0x0RGB` format used by the game.
+    ///
+    /// Returns `None` if the color can't be represented exactly, see
+    /// [`Color::to_12_bit_lossy`] to round it instead.
+    pub fn to_12_bit(&self) -> Option<u16> {
+        self.is_12_bit().then(|| self.to_12_bit_lossy())
+    }
+
+    /// Packs the color into the 12-bit `0x0RGB` format used by the game,
+    /// rounding every channel to the closest representable value.
+    pub fn to_12_bit_lossy(&self) -> u16 {
+        // 12-bit channels are scaled by 0x11 when decoding.
+        let nibble = |channel: u8| (channel as u16 + 0x11 / 2) / 0x11;
+
+        (nibble(self.r) << 8) | (nibble(self.g) << 4) | nibble(self.b)
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        ((self.r as u32) << 16) | ((self.g as u32) << 8) | self.b as u32
+    }
+
+    /// Whether the color can be represented exactly in the 12-bit color space
+    /// of the game (see [`Color::from_12_bit`]), which requires both nibbles of
+    /// every channel to be the same.
+    pub fn is_12_bit(&self) -> bool {
+        [self.r, self.g, self.b]
+            .iter()
+            .all(|channel| channel >> 4 == channel & 0xF)
+    }
+}
+
+impl Parser for Color {
+    type Context<'ctx> = ();
+
+    fn parser((): Self::Context<'_>) -> impl Fn(Input) -> Result<Self> {
+        move |input| {
+            let (input, color) = number::le_u32(input)?;
+            Ok((input, Self::from_12_bit(color as u16)))
+        }
+    }
+}
+
+// TODO(Unavailable): derive ???
+pub struct ColorMap {
+    // TODO(nenikitov): This probably shouldn't be `pub` and should have an
+    // accessor that will hide the "ugly" internal 2D-array structure.
+    pub shades: Box<[[Color; COLORS_COUNT]; SHADES_COUNT]>,
+}
+
+impl Parser for ColorMap {
+    type Context<'ctx> = ();
+
+    fn parser((): Self::Context<'_>) -> impl Fn(Input) -> Result<Self> {
+        move |input| {
+            error::ensure_bytes_length(
+                input,
+                size_of::<u32>() * COLORS_COUNT * SHADES_COUNT,
+                "Incorrect `ColorMap` format (256x32 array of 12-bit [padded to 32-bit] colors)",
+            )?;
+
+            let (input, colors) = multi::count!(
+                |input| -> Result<[Color; COLORS_COUNT]> {
+                    multi::count!(Color::parser(()))(input)
+                },
+                SHADES_COUNT
+            )(input)?;
+
+            let colors = {
+                let colors = colors.into_boxed_slice();
+                // Ensure the original box is not dropped.
+                let mut colors = std::mem::ManuallyDrop::new(colors);
+                // SAFETY: [_] and [_; N] has the same memory layout as long
+                // as the slice contains exactly N elements.
+                unsafe { Box::from_raw(colors.as_mut_ptr().cast()) }
+            };
+
+            Ok((input, Self { shades: colors }))
+        }
+    }
+}
+
+impl ColorMap {
+    /// Encodes the color map in the same format the game stores it in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any color doesn't fit in the 12-bit color space,
+    /// since it would change when parsed back.
+    pub fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        self.shades
+            .iter()
+            .enumerate()
+            .flat_map(|(shade, colors)| colors.iter().enumerate().map(move |(i, c)| (shade, i, c)))
+            .map(|(shade, i, color)| {
+                let color = color.to_12_bit().ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "Color #{:0>6X} at index {i} of shade {shade} doesn't fit in 12-bit color space",
+                            color.to_u32()
+                        ),
+                    )
+                })?;
+                Ok(u32::to_le_bytes(color as u32))
+            })
+            .flatten_ok()
+            .collect()
+    }
+
+    /// Lays out the shades as raw RGBA pixels, one row per shade.
+    pub fn to_rgba(&self, gamma: Option<Gamma>) -> RgbaBuffer {
+        match gamma {
+            Some(gamma) => gamma.table.bake(gamma.level, self).to_rgba(None),
+            None => RgbaBuffer::from_colors(self.shades.as_slice()),
+        }
+    }
+
+    #[cfg(feature = "conv")]
+    pub fn to_png<W>(&self, mut write: W, gamma: Option<Gamma>) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        use crate::utils::format::PngFile;
+        match gamma {
+            Some(gamma) => gamma.table.bake(gamma.level, self).to_png(write, None),
+            None => write.write_all(&self.shades.as_slice().to_png()),
+        }
+    }
+
+    /// Writes a single shade as a palette file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    #[cfg(feature = "conv")]
+    pub fn shade_to_palette<W>(
+        &self,
+        writer: W,
+        shade: usize,
+        format: PaletteFormat,
+    ) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        write_palette(
+            writer,
+            &self.shades[shade],
+            &format!("Shade {shade}"),
+            format,
+        )
+    }
+
+    /// Builds a color map from the palette files of all 32 shades, from the
+    /// darkest to the brightest.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a palette can't be read, if it doesn't have exactly
+    /// 256 colors, or if there aren't exactly 32 palettes.
+    #[cfg(feature = "conv")]
+    pub fn from_palettes<R, I>(readers: I, format: PaletteFormat) -> std::io::Result<Self>
+    where
+        R: std::io::Read,
+        I: IntoIterator<Item = R>,
+    {
+        use std::io::{Error, ErrorKind};
+
+        let shades = readers
+            .into_iter()
+            .map(|reader| {
+                <[Color; COLORS_COUNT]>::try_from(read_palette(reader, format)?).map_err(|p| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Shade has {} colors instead of {COLORS_COUNT}", p.len()),
+                    )
+                })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let shades =
+            <Box<[_; SHADES_COUNT]>>::try_from(shades.into_boxed_slice()).map_err(|s| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Color map has {} shades instead of {SHADES_COUNT}", s.len()),
+                )
+            })?;
+
+        Ok(Self { shades })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::LazyCell;
+
+    use super::*;
+    use crate::utils::test::*;
+
+    #[test]
+    fn shade_works() -> eyre::Result<()> {
+        assert_eq!(
+            Color::parser(())(&u32::to_le_bytes(0x100))?.1,
+            Color {
+                r: 0x11,
+                g: 0,
+                b: 0
+            },
+        );
+        assert_eq!(
+            Color::parser(())(&u32::to_le_bytes(0x011))?.1,
+            Color {
+                r: 0,
+                g: 0x11,
+                b: 0x11
+            },
+        );
+        assert_eq!(
+            Color::parser(())(&u32::to_le_bytes(0x001))?.1,
+            Color {
+                r: 0,
+                g: 0,
+                b: 0x11
+            },
+        );
+        assert_eq!(
+            Color::parser(())(&u32::to_le_bytes(0x220))?.1,
+            Color {
+                r: 0x22,
+                g: 0x22,
+                b: 0x00
+            },
+        );
+        assert_eq!(
+            Color::parser(())(&u32::to_le_bytes(0x022))?.1,
+            Color {
+                r: 0,
+                g: 0x22,
+                b: 0x22
+            },
+        );
+        assert_eq!(
+            Color::parser(())(&u32::to_le_bytes(0x333))?.1,
+            Color {
+                r: 0x33,
+                g: 0x33,
+                b: 0x33
+            },
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_12_bit_works() {
+        assert_eq!(Color::from_12_bit(0x1AF).to_12_bit(), Some(0x1AF));
+        assert_eq!(
+            Color {
+                r: 0x12,
+                g: 0x88,
+                b: 0xFE
+            }
+            .to_12_bit(),
+            None
+        );
+        assert_eq!(
+            Color {
+                r: 0x12,
+                g: 0x88,
+                b: 0xF7
+            }
+            .to_12_bit_lossy(),
+            0x18F
+        );
+    }
+
+    #[test]
+    fn to_bytes_works() -> eyre::Result<()> {
+        let bytes = (0..COLORS_COUNT * SHADES_COUNT)
+            .flat_map(|i| u32::to_le_bytes(i as u32 % 0x1000))
+            .collect::<Vec<_>>();
+        let (_, mut color_map) = ColorMap::parser(())(&bytes)?;
+
+        assert_eq!(color_map.to_bytes()?, bytes);
+
+        color_map.shades[3][7] = Color { r: 1, g: 2, b: 3 };
+        let error = color_map.to_bytes().unwrap_err();
+        assert!(error.to_string().contains("index 7 of shade 3"));
+
+        Ok(())
+    }
+
+    const COLOR_MAPS: LazyCell<Vec<(&str, Vec<u8>)>> = LazyCell::new(|| {
+        vec![
+            ("creature", deflated_file!("01.dat")),
+            ("creature-ghost", deflated_file!("02.dat")),
+            ("ghost-creature-ghost", deflated_file!("03.dat")),
+            ("pickup", deflated_file!("04.dat")),
+            ("pickup-ghost", deflated_file!("05.dat")),
+            ("jacob", deflated_file!("06.dat")),
+            ("level-ghost", deflated_file!("07.dat")),
+            ("player-hands", deflated_file!("08.dat")),
+            ("player-hands-ghost", deflated_file!("09.dat")),
+            ("level1a", deflated_file!("4F.dat")),
+            ("level1b", deflated_file!("53.dat")),
+            ("level2a", deflated_file!("57.dat")),
+            ("level2b", deflated_file!("5B.dat")),
+            ("level3a", deflated_file!("5F.dat")),
+            ("level3b", deflated_file!("63.dat")),
+            ("level4a", deflated_file!("67.dat")),
+            ("level4b", deflated_file!("6B.dat")),
+            ("level5a", deflated_file!("6F.dat")),
+            ("level5b", deflated_file!("73.dat")),
+            ("level6", deflated_file!("77.dat")),
+            ("level7", deflated_file!("7B.dat")),
+            ("level8", deflated_file!("7F.dat")),
+            ("leveldm1", deflated_file!("82.dat")),
+            ("leveldm2", deflated_file!("85.dat")),
+            ("leveldm3", deflated_file!("88.dat")),
+            ("leveldm4", deflated_file!("8B.dat")),
+        ]
+    });
+
+    #[test]
+    #[ignore = "uses Ashen ROM files"]
+    fn to_bytes_matches_rom() -> eyre::Result<()> {
+        let color_maps = COLOR_MAPS;
+        color_maps.iter().try_for_each(|(name, data)| {
+            let (_, color_map) = ColorMap::parser(())(data)?;
+
+            assert!(
+                color_map.to_bytes()? == *data,
+                "{name} is not byte-identical"
+            );
+
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "conv")]
+    #[test]
+    #[ignore = "uses Ashen ROM files"]
+    fn parse_rom_asset() -> eyre::Result<()> {
+        let color_map_path = PARSED_PATH.join("color-map");
+        COLOR_MAPS.iter().try_for_each(|(name, data)| {
+            let (_, color_map) = ColorMap::parser(())(data)?;
+
+            output_file(color_map_path.join(format!("{name}.png")))
+                .and_then(|w| color_map.to_png(w, None))?;
+
+            for format in [
+                PaletteFormat::Gpl,
+                PaletteFormat::Act,
+                PaletteFormat::Pal,
+                PaletteFormat::Json,
+            ] {
+                let palettes = (0..SHADES_COUNT)
+                    .map(|shade| {
+                        let mut bytes = vec![];
+                        color_map.shade_to_palette(&mut bytes, shade, format)?;
+                        Ok(bytes)
+                    })
+                    .collect::<std::io::Result<Vec<_>>>()?;
+
+                output_file(color_map_path.join(format!("{name}.{}", format.extension())))
+                    .and_then(|mut w| w.write_all(&palettes[15]))?;
+
+                let imported = ColorMap::from_palettes(palettes.iter().map(Vec::as_slice), format)?;
+                assert_eq!(imported.shades, color_map.shades);
+            }
+
+            Ok(())
+        })
+    }
+}
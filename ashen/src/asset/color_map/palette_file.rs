@@ -0,0 +1,290 @@
+//! Palette files understood by image editors.
+
+use std::io::{self, BufRead, BufReader, Error, ErrorKind, Read, Write};
+
+use itertools::Itertools;
+
+use super::Color;
+
+/// File formats a palette can be exported to and imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// GIMP palette (`.gpl`), also used by Aseprite and Krita.
+    Gpl,
+    /// Adobe Color Table (`.act`), used by Photoshop.
+    Act,
+    /// JASC palette (`.pal`), used by Paint Shop Pro and Aseprite.
+    Pal,
+    /// JSON array of `#RRGGBB` strings.
+    Json,
+}
+
+impl PaletteFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            PaletteFormat::Gpl => "gpl",
+            PaletteFormat::Act => "act",
+            PaletteFormat::Pal => "pal",
+            PaletteFormat::Json => "json",
+        }
+    }
+}
+
+/// Writes `palette` as a palette file, `name` is only used by formats that
+/// support it.
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_palette<W>(
+    mut writer: W,
+    palette: &[Color],
+    name: &str,
+    format: PaletteFormat,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    match format {
+        PaletteFormat::Gpl => {
+            writeln!(writer, "GIMP Palette")?;
+            writeln!(writer, "Name: {name}")?;
+            writeln!(writer, "Columns: 16")?;
+            writeln!(writer, "#")?;
+            palette.iter().enumerate().try_for_each(|(i, c)| {
+                writeln!(writer, "{:>3} {:>3} {:>3}\tIndex {i}", c.r, c.g, c.b)
+            })
+        }
+        PaletteFormat::Act => {
+            // Tables with less than 256 colors are padded, the actual count is
+            // stored in the 4 byte trailer.
+            let colors = palette
+                .iter()
+                .flat_map(|c| [c.r, c.g, c.b])
+                .pad_using(256 * 3, |_| 0)
+                .collect::<Vec<_>>();
+            writer.write_all(&colors)?;
+            writer.write_all(&(palette.len() as u16).to_be_bytes())?;
+            writer.write_all(&u16::MAX.to_be_bytes())
+        }
+        PaletteFormat::Pal => {
+            write!(writer, "JASC-PAL\r\n0100\r\n{}\r\n", palette.len())?;
+            palette
+                .iter()
+                .try_for_each(|c| write!(writer, "{} {} {}\r\n", c.r, c.g, c.b))
+        }
+        PaletteFormat::Json => {
+            let colors = palette
+                .iter()
+                .map(|c| format!("#{:0>6X}", c.to_u32()))
+                .collect::<Vec<_>>();
+            serde_json::to_writer_pretty(&mut writer, &colors)?;
+            Ok(())
+        }
+    }
+}
+
+/// Reads a palette file.
+///
+/// Every color should be representable in the 12-bit color space used by the
+/// game, see [`Color::is_12_bit`].
+///
+/// # Errors
+///
+/// Returns an error if the file isn't valid for `format`, or if a color
+/// doesn't fit in the 12-bit color space.
+pub fn read_palette<R>(reader: R, format: PaletteFormat) -> io::Result<Vec<Color>>
+where
+    R: Read,
+{
+    let palette = match format {
+        PaletteFormat::Gpl => read_gpl(reader)?,
+        PaletteFormat::Act => read_act(reader)?,
+        PaletteFormat::Pal => read_pal(reader)?,
+        PaletteFormat::Json => read_json(reader)?,
+    };
+
+    if let Some((i, c)) = palette.iter().find_position(|c| !c.is_12_bit()) {
+        return Err(invalid_data(format!(
+            "Color #{:0>6X} at index {i} doesn't fit in 12-bit color space",
+            c.to_u32()
+        )));
+    }
+
+    Ok(palette)
+}
+
+fn invalid_data<E>(error: E) -> Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    Error::new(ErrorKind::InvalidData, error)
+}
+
+fn parse_channel(channel: Option<&str>) -> io::Result<u8> {
+    channel
+        .ok_or_else(|| invalid_data("Missing color channel"))?
+        .parse()
+        .map_err(invalid_data)
+}
+
+fn read_gpl<R: Read>(reader: R) -> io::Result<Vec<Color>> {
+    let mut lines = BufReader::new(reader).lines();
+
+    if lines.next().transpose()?.as_deref().map(str::trim) != Some("GIMP Palette") {
+        return Err(invalid_data("Missing `GIMP Palette` header"));
+    }
+
+    lines
+        .filter_ok(|line| {
+            let line = line.trim();
+            !(line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("Name:")
+                || line.starts_with("Columns:"))
+        })
+        .map(|line| {
+            let line = line?;
+            let mut channels = line.split_whitespace();
+            Ok(Color {
+                r: parse_channel(channels.next())?,
+                g: parse_channel(channels.next())?,
+                b: parse_channel(channels.next())?,
+            })
+        })
+        .collect()
+}
+
+fn read_act<R: Read>(mut reader: R) -> io::Result<Vec<Color>> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+
+    let count = match bytes.len() {
+        768 => 256,
+        772 => u16::from_be_bytes([bytes[768], bytes[769]]).min(256) as usize,
+        len => {
+            return Err(invalid_data(format!(
+                "Color table should be 768 or 772 bytes long, found {len}"
+            )));
+        }
+    };
+
+    Ok(bytes.as_chunks().0[..count]
+        .iter()
+        .map(|&[r, g, b]| Color { r, g, b })
+        .collect())
+}
+
+fn read_pal<R: Read>(reader: R) -> io::Result<Vec<Color>> {
+    let mut lines = BufReader::new(reader).lines();
+    let mut next_line = || {
+        lines
+            .next()
+            .transpose()?
+            .ok_or_else(|| invalid_data("Unexpected end of palette"))
+    };
+
+    if next_line()?.trim() != "JASC-PAL" {
+        return Err(invalid_data("Missing `JASC-PAL` header"));
+    }
+    let _version = next_line()?;
+    let count = next_line()?.trim().parse::<usize>().map_err(invalid_data)?;
+
+    (0..count)
+        .map(|_| {
+            let line = next_line()?;
+            let mut channels = line.split_whitespace();
+            Ok(Color {
+                r: parse_channel(channels.next())?,
+                g: parse_channel(channels.next())?,
+                b: parse_channel(channels.next())?,
+            })
+        })
+        .collect()
+}
+
+fn read_json<R: Read>(reader: R) -> io::Result<Vec<Color>> {
+    let colors: Vec<String> = serde_json::from_reader(reader)?;
+
+    colors
+        .iter()
+        .map(|color| {
+            let hex = color
+                .strip_prefix('#')
+                .filter(|hex| hex.len() == 6)
+                .ok_or_else(|| invalid_data(format!("Color `{color}` is not `#RRGGBB`")))?;
+            let color = u32::from_str_radix(hex, 16).map_err(invalid_data)?;
+
+            Ok(Color {
+                r: (color >> 16) as u8,
+                g: (color >> 8) as u8,
+                b: color as u8,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette() -> Vec<Color> {
+        (0..256).map(|i| Color::from_12_bit(i * 0x10 + 1)).collect()
+    }
+
+    #[test]
+    fn round_trip_works() -> eyre::Result<()> {
+        let palette = palette();
+
+        for format in [
+            PaletteFormat::Gpl,
+            PaletteFormat::Act,
+            PaletteFormat::Pal,
+            PaletteFormat::Json,
+        ] {
+            let mut bytes = vec![];
+            write_palette(&mut bytes, &palette, "Test", format)?;
+            assert_eq!(
+                read_palette(bytes.as_slice(), format)?,
+                palette,
+                "{format:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_gpl_works() -> eyre::Result<()> {
+        let gpl =
+            "GIMP Palette\nName: Test\nColumns: 4\n# Comment\n\n  0  17 255\tBlue\n 34 51 68\n";
+
+        assert_eq!(
+            read_palette(gpl.as_bytes(), PaletteFormat::Gpl)?,
+            [
+                Color {
+                    r: 0,
+                    g: 0x11,
+                    b: 0xFF
+                },
+                Color {
+                    r: 0x22,
+                    g: 0x33,
+                    b: 0x44
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_rejects_non_12_bit() {
+        let json = r##"["#000000", "#123456"]"##;
+
+        let error = read_palette(json.as_bytes(), PaletteFormat::Json).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("index 1"));
+    }
+}
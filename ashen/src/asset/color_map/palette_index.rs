@@ -0,0 +1,236 @@
+use super::Color;
+
+/// How the distance between two colors is measured by a [`PaletteIndex`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDistance {
+    /// Euclidean distance between the RGB channels.
+    Rgb,
+    /// Euclidean distance in the CIELAB color space (CIE76), which is closer
+    /// to how different colors look.
+    Lab,
+}
+
+impl ColorDistance {
+    /// The distance between two colors.
+    pub fn between(self, a: Color, b: Color) -> f32 {
+        match self {
+            ColorDistance::Rgb => rgb_distance(a, b),
+            ColorDistance::Lab => lab_distance(to_lab(a), to_lab(b)),
+        }
+        .sqrt()
+    }
+}
+
+/// Reverse lookup from colors to the closest index of a palette.
+///
+/// The closest index of every color of the 12-bit color space used by the game
+/// is computed up front, so looking up colors that come from the game is a
+/// single table access. Other colors are compared with the whole palette.
+pub struct PaletteIndex {
+    palette: Vec<Color>,
+    distance: ColorDistance,
+    lab: Vec<[f32; 3]>,
+    table: Box<[u8; 0x1000]>,
+}
+
+impl PaletteIndex {
+    /// Builds the index of a palette (such as a [`ColorMap`] shade or a
+    /// [`Skybox`] palette).
+    ///
+    /// If a color appears more than once, the first index is used.
+    ///
+    /// # Panics
+    ///
+    /// If the palette is empty or has more than 256 colors.
+    ///
+    /// [`ColorMap`]: super::ColorMap
+    /// [`Skybox`]: crate::asset::skybox::Skybox
+    pub fn new(palette: &[Color], distance: ColorDistance) -> Self {
+        assert!(
+            (1..=256).contains(&palette.len()),
+            "palette should have between 1 and 256 colors"
+        );
+
+        let mut index = Self {
+            palette: palette.to_vec(),
+            distance,
+            lab: palette.iter().map(|&color| to_lab(color)).collect(),
+            table: Box::new([0; 0x1000]),
+        };
+        for color in 0..0x1000 {
+            index.table[color] = index.search(Color::from_12_bit(color as u16));
+        }
+
+        index
+    }
+
+    pub fn palette(&self) -> &[Color] {
+        &self.palette
+    }
+
+    /// The index of `color` in the palette, if it's there.
+    pub fn exact(&self, color: Color) -> Option<u8> {
+        let index = self.nearest(color);
+        (self.palette[index as usize] == color).then_some(index)
+    }
+
+    /// The index of the palette color that is the closest to `color`.
+    pub fn nearest(&self, color: Color) -> u8 {
+        match color.to_12_bit() {
+            Some(color) => self.table[color as usize],
+            None => self.search(color),
+        }
+    }
+
+    /// The distance between `color` and the closest palette color.
+    pub fn nearest_distance(&self, color: Color) -> f32 {
+        let index = self.nearest(color) as usize;
+
+        match self.distance {
+            ColorDistance::Rgb => rgb_distance(self.palette[index], color),
+            ColorDistance::Lab => lab_distance(self.lab[index], to_lab(color)),
+        }
+        .sqrt()
+    }
+
+    fn search(&self, color: Color) -> u8 {
+        // Distances are compared squared, the square root doesn't change the
+        // order.
+        let closest = match self.distance {
+            ColorDistance::Rgb => self
+                .palette
+                .iter()
+                .map(|&c| rgb_distance(c, color))
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.total_cmp(b)),
+            ColorDistance::Lab => {
+                let color = to_lab(color);
+                self.lab
+                    .iter()
+                    .map(|&c| lab_distance(c, color))
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            }
+        };
+
+        closest.map(|(i, _)| i as u8).expect("Palette is not empty")
+    }
+}
+
+fn rgb_distance(a: Color, b: Color) -> f32 {
+    let r = a.r as f32 - b.r as f32;
+    let g = a.g as f32 - b.g as f32;
+    let b = a.b as f32 - b.b as f32;
+    r * r + g * g + b * b
+}
+
+fn lab_distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|i| (a[i] - b[i]).powi(2)).sum()
+}
+
+/// Converts an sRGB color to CIELAB, using the D65 white point.
+fn to_lab(color: Color) -> [f32; 3] {
+    let linear = |channel: u8| {
+        let channel = channel as f32 / 255.0;
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (red, green, blue) = (linear(color.r), linear(color.g), linear(color.b));
+
+    let x = (0.4124 * red + 0.3576 * green + 0.1805 * blue) / 0.95047;
+    let y = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+    let z = (0.0193 * red + 0.1192 * green + 0.9505 * blue) / 1.08883;
+
+    let compand = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (x, y, z) = (compand(x), compand(y), compand(z));
+
+    [116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette() -> Vec<Color> {
+        vec![
+            Color { r: 0, g: 0, b: 0 },
+            Color {
+                r: 0xFF,
+                g: 0xFF,
+                b: 0xFF,
+            },
+            Color {
+                r: 0xFF,
+                g: 0,
+                b: 0,
+            },
+            Color {
+                r: 0,
+                g: 0,
+                b: 0x88,
+            },
+            Color {
+                r: 0xFF,
+                g: 0,
+                b: 0,
+            },
+        ]
+    }
+
+    #[test]
+    fn exact_works() {
+        let index = PaletteIndex::new(&palette(), ColorDistance::Rgb);
+
+        assert_eq!(index.exact(Color { r: 0, g: 0, b: 0 }), Some(0));
+        assert_eq!(
+            index.exact(Color {
+                r: 0xFF,
+                g: 0,
+                b: 0
+            }),
+            Some(2)
+        );
+        assert_eq!(index.exact(Color { r: 0, g: 0, b: 1 }), None);
+    }
+
+    #[test]
+    fn nearest_works() {
+        for distance in [ColorDistance::Rgb, ColorDistance::Lab] {
+            let index = PaletteIndex::new(&palette(), distance);
+
+            assert_eq!(
+                index.nearest(Color {
+                    r: 0xEE,
+                    g: 0xDD,
+                    b: 0xEE
+                }),
+                1
+            );
+            assert_eq!(
+                index.nearest(Color {
+                    r: 0xC3,
+                    g: 0x20,
+                    b: 0x17
+                }),
+                2
+            );
+            assert_eq!(
+                index.nearest(Color {
+                    r: 0x10,
+                    g: 0x10,
+                    b: 0x70
+                }),
+                3
+            );
+        }
+    }
+}
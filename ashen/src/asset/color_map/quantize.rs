@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+
+use super::Color;
+
+/// Picks a palette of at most `count` colors that represents `colors` well,
+/// using the median cut algorithm.
+///
+/// Colors are rounded to the 12-bit color space of the game first, so if there
+/// are no more than `count` distinct colors after that, the palette will
+/// contain all of them.
+pub fn median_cut<I>(colors: I, count: usize) -> Vec<Color>
+where
+    I: IntoIterator<Item = Color>,
+{
+    let mut histogram = BTreeMap::<u16, usize>::new();
+    for color in colors {
+        *histogram.entry(color.to_12_bit_lossy()).or_default() += 1;
+    }
+    let histogram = histogram
+        .into_iter()
+        .map(|(color, population)| (Color::from_12_bit(color), population))
+        .collect::<Vec<_>>();
+
+    if histogram.len() <= count {
+        return histogram.into_iter().map(|(color, _)| color).collect();
+    }
+
+    let mut boxes = vec![histogram];
+    while boxes.len() < count {
+        // Split the box that covers the most pixels over the widest range, so
+        // busy areas of the color space get more colors.
+        let Some((i, channel, _)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(i, colors)| {
+                let (channel, range) = widest_channel(colors);
+                (i, channel, range as usize * population(colors))
+            })
+            .max_by_key(|&(_, _, score)| score)
+        else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(i);
+        colors.sort_by_key(|&(color, _)| channel_value(color, channel));
+
+        let half = population(&colors) / 2;
+        let median = colors
+            .iter()
+            .scan(0, |sum, (_, population)| {
+                *sum += population;
+                Some(*sum)
+            })
+            .position(|sum| sum >= half)
+            .unwrap_or(0);
+        let upper = colors.split_off((median + 1).min(colors.len() - 1));
+
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(Vec::as_slice)
+        .map(average)
+        .unique()
+        .collect()
+}
+
+fn channel_value(color: Color, channel: usize) -> u8 {
+    [color.r, color.g, color.b][channel]
+}
+
+fn population(colors: &[(Color, usize)]) -> usize {
+    colors.iter().map(|(_, population)| population).sum()
+}
+
+fn widest_channel(colors: &[(Color, usize)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = colors
+                .iter()
+                .map(|&(color, _)| channel_value(color, channel))
+                .minmax()
+                .into_option()
+                .unwrap_or((0, 0));
+            (channel, max - min)
+        })
+        .max_by_key(|&(_, range)| range)
+        .expect("There are 3 channels")
+}
+
+/// The mean color of a box, weighted by population and rounded to 12-bit.
+fn average(colors: &[(Color, usize)]) -> Color {
+    let total = population(colors).max(1);
+    let mean = |channel: usize| {
+        let sum: usize = colors
+            .iter()
+            .map(|&(color, population)| channel_value(color, channel) as usize * population)
+            .sum();
+        ((sum + total / 2) / total) as u8
+    };
+
+    let color = Color {
+        r: mean(0),
+        g: mean(1),
+        b: mean(2),
+    };
+    Color::from_12_bit(color.to_12_bit_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_cut_keeps_few_colors() {
+        let colors = [0x123, 0xFFF, 0x123, 0x000].map(Color::from_12_bit);
+
+        let palette = median_cut(colors, 256);
+
+        assert_eq!(palette, [0x000, 0x123, 0xFFF].map(Color::from_12_bit));
+    }
+
+    #[test]
+    fn median_cut_works() {
+        let colors = (0..0x1000).map(Color::from_12_bit);
+
+        let palette = median_cut(colors, 16);
+
+        assert_eq!(palette.len(), 16);
+        assert!(palette.iter().all(Color::is_12_bit));
+    }
+}
@@ -1,4 +1,9 @@
-use super::Parser;
+use std::borrow::Cow;
+
+use super::{
+    Parser,
+    color_map::{Color, ColorMap},
+};
 use crate::{error, utils::nom::*};
 
 const ROWS_COUNT: usize = 256;
@@ -40,12 +45,45 @@ impl Parser for GammaTable {
 }
 
 impl GammaTable {
+    /// The highest gamma level, matching the maximum of the brightness slider
+    /// in the settings.
+    pub const MAX_LEVEL: usize = COLS_COUNT - 1;
+
+    /// Corrects every channel of `color` for a gamma `level` (0-100).
+    ///
+    /// Levels above [`Self::MAX_LEVEL`] are clamped.
+    pub fn apply(&self, level: usize, color: Color) -> Color {
+        let lookup = &self.lookups[level.min(Self::MAX_LEVEL)];
+
+        Color {
+            r: lookup[color.r as usize],
+            g: lookup[color.g as usize],
+            b: lookup[color.b as usize],
+        }
+    }
+
+    /// Encodes the gamma table in the same format the game stores it in.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.lookups.as_flattened().to_vec()
+    }
+
+    /// Bakes a gamma `level` (0-100) into every shade of `color_map`.
+    pub fn bake(&self, level: usize, color_map: &ColorMap) -> ColorMap {
+        ColorMap {
+            shades: Box::new(
+                color_map
+                    .shades
+                    .map(|shade| shade.map(|color| self.apply(level, color))),
+            ),
+        }
+    }
+
     #[cfg(feature = "conv")]
     pub fn to_png<W>(&self, mut writer: W) -> std::io::Result<()>
     where
         W: std::io::Write,
     {
-        use crate::{asset::color_map::Color, utils::format::PngFile};
+        use crate::utils::format::PngFile;
         let bytes = self
             .lookups
             .to_vec()
@@ -65,8 +103,37 @@ impl GammaTable {
     }
 }
 
+/// A gamma level to apply when exporting images, emulating the brightness
+/// setting of the game.
+#[derive(Clone, Copy)]
+pub struct Gamma<'a> {
+    pub table: &'a GammaTable,
+    /// Gamma level (0-100).
+    pub level: usize,
+}
+
+impl Gamma<'_> {
+    /// Corrects every color of `palette`.
+    pub fn apply_palette(&self, palette: &[Color]) -> Vec<Color> {
+        palette
+            .iter()
+            .map(|color| self.table.apply(self.level, *color))
+            .collect()
+    }
+}
+
+/// Applies `gamma` to `palette`, if any.
+pub(crate) fn corrected_palette<'p>(
+    palette: &'p [Color],
+    gamma: Option<Gamma>,
+) -> Cow<'p, [Color]> {
+    match gamma {
+        Some(gamma) => Cow::Owned(gamma.apply_palette(palette)),
+        None => Cow::Borrowed(palette),
+    }
+}
+
 #[cfg(test)]
-#[cfg(feature = "conv")]
 mod tests {
     use std::cell::LazyCell;
 
@@ -75,13 +142,44 @@ mod tests {
 
     const GAMMA_TABLE: LazyCell<Vec<u8>> = LazyCell::new(|| deflated_file!("00.dat"));
 
+    #[test]
+    fn apply_works() -> eyre::Result<()> {
+        let bytes = (0..COLS_COUNT)
+            .flat_map(|level| (0..ROWS_COUNT).map(move |i| (i * level / 100) as u8))
+            .collect::<Vec<_>>();
+        let (_, gamma_table) = GammaTable::parser(())(&bytes)?;
+
+        let color = Color {
+            r: 200,
+            g: 100,
+            b: 10,
+        };
+        assert_eq!(gamma_table.apply(100, color), color);
+        assert_eq!(
+            gamma_table.apply(50, color),
+            Color {
+                r: 100,
+                g: 50,
+                b: 5
+            }
+        );
+        assert_eq!(gamma_table.apply(1000, color), color);
+        assert_eq!(gamma_table.to_bytes(), bytes);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "conv")]
     #[test]
     #[ignore = "uses Ashen ROM files"]
     fn parse_rom_asset() -> eyre::Result<()> {
-        let (_, gamma_table) = GammaTable::parser(())(&GAMMA_TABLE)?;
+        let data = GAMMA_TABLE;
+        let (_, gamma_table) = GammaTable::parser(())(&data)?;
 
         output_file(PARSED_PATH.join("gamma-table.png")).and_then(|w| gamma_table.to_png(w))?;
 
+        assert_eq!(gamma_table.to_bytes(), *data);
+
         Ok(())
     }
 }
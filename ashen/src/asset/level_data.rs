@@ -0,0 +1,103 @@
+use fixed::types::I16F16;
+
+use super::Parser;
+use crate::utils::nom::*;
+
+/// Per-level settings hardcoded in the executable (`DawnLevels::LevelData`).
+///
+/// Asset ids are indices of files in the pack file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelData {
+    pub index: i32,
+    pub level_asset_id: u32,
+    pub level_collision_asset_id: u32,
+    pub level_waypoint_asset_id: i32,
+    pub color_map_asset_id: u32,
+    pub sky_asset_id: u32,
+    pub sky_ghost_palette_asset_id: u32,
+    /// How many times the sky texture wraps around the horizon.
+    pub sky_horizontal_texture_revolutions: I16F16,
+    /// How many times the sky texture fits between the top and the bottom of
+    /// the view sphere.
+    pub sky_vertical_texture_revolutions: I16F16,
+    /// Vertical shift of the sky texture, in texels.
+    pub sky_vertical_offset: i32,
+}
+
+impl Parser for LevelData {
+    type Context<'ctx> = ();
+
+    fn parser((): Self::Context<'_>) -> impl Fn(Input) -> Result<Self> {
+        move |input| {
+            let (input, index) = number::le_i32(input)?;
+            let (input, level_asset_id) = number::le_u32(input)?;
+            let (input, level_collision_asset_id) = number::le_u32(input)?;
+            let (input, level_waypoint_asset_id) = number::le_i32(input)?;
+            let (input, color_map_asset_id) = number::le_u32(input)?;
+            let (input, sky_asset_id) = number::le_u32(input)?;
+            let (input, sky_ghost_palette_asset_id) = number::le_u32(input)?;
+            let (input, sky_horizontal_texture_revolutions) = number::le_i16f16(input)?;
+            let (input, sky_vertical_texture_revolutions) = number::le_i16f16(input)?;
+            let (input, sky_vertical_offset) = number::le_i32(input)?;
+
+            Ok((
+                input,
+                Self {
+                    index,
+                    level_asset_id,
+                    level_collision_asset_id,
+                    level_waypoint_asset_id,
+                    color_map_asset_id,
+                    sky_asset_id,
+                    sky_ghost_palette_asset_id,
+                    sky_horizontal_texture_revolutions,
+                    sky_vertical_texture_revolutions,
+                    sky_vertical_offset,
+                },
+            ))
+        }
+    }
+}
+
+/// The level table, as laid out in the executable.
+impl Parser for Vec<LevelData> {
+    type Context<'ctx> = ();
+
+    fn parser((): Self::Context<'_>) -> impl Fn(Input) -> Result<Self> {
+        move |input| multi::many0(LevelData::parser(()))(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser_works() -> eyre::Result<()> {
+        let bytes = [
+            0i32.to_le_bytes(),
+            0x4Cu32.to_le_bytes(),
+            0x4Du32.to_le_bytes(),
+            (-1i32).to_le_bytes(),
+            0x4Fu32.to_le_bytes(),
+            0x3Cu32.to_le_bytes(),
+            0x42u32.to_le_bytes(),
+            0x0002_0000i32.to_le_bytes(),
+            0x0000_8000i32.to_le_bytes(),
+            (-16i32).to_le_bytes(),
+        ]
+        .concat();
+
+        let (_, levels) = Vec::<LevelData>::parser(())(&[bytes.as_slice(), &bytes].concat())?;
+
+        assert_eq!(levels.len(), 2);
+        assert_eq!(levels[0].level_waypoint_asset_id, -1);
+        assert_eq!(levels[0].sky_asset_id, 0x3C);
+        assert_eq!(levels[0].sky_ghost_palette_asset_id, 0x42);
+        assert_eq!(levels[0].sky_horizontal_texture_revolutions, 2);
+        assert_eq!(levels[0].sky_vertical_texture_revolutions, 0.5);
+        assert_eq!(levels[0].sky_vertical_offset, -16);
+
+        Ok(())
+    }
+}
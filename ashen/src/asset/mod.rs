@@ -1,8 +1,10 @@
 pub mod color_map;
 pub mod gamma_table;
+pub mod level_data;
 pub mod model;
 pub mod pack_file;
 pub mod pack_info;
+pub mod skin;
 pub mod skybox;
 pub mod sound;
 pub mod string_table;
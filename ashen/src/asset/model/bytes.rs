@@ -0,0 +1,456 @@
+//! Binary writer, the reverse of the [`Model`] parser.
+
+use std::io::{self, Error, ErrorKind};
+
+use fixed::types::I24F8;
+
+use super::{
+    Model, ModelIssue,
+    dat::{
+        frame::{ModelFrame, VertexTransform},
+        triangle::TextureDimensions,
+    },
+};
+
+pub(super) const HEADER_SIZE: usize = 60;
+pub(super) const TRIANGLE_SIZE: usize = 18;
+pub(super) const SEQUENCE_SIZE: usize = 8;
+/// Size of the transform and of the bounding sphere radius.
+pub(super) const FRAME_HEADER_SIZE: usize = 28;
+
+/// How far written vertices are from the positions they were written from, in
+/// meters.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct QuantizationError {
+    pub max: f32,
+    pub mean: f32,
+}
+
+impl Model {
+    /// Writes the model in the format it's parsed from.
+    ///
+    /// Vertices are stored as 8-bit coordinates scaled by a transform of their
+    /// frame. Frames that still fit the transform they were parsed with keep
+    /// it (along with their bounding sphere), so unchanged models are written
+    /// back as they were. Other frames get a new transform that spans their
+    /// vertices, which moves them slightly, by up to the returned error.
+    ///
+    /// Sections are written where the [layout](Model::layout) of parsed models
+    /// put them, with the same padding and locator slots, as long as they
+    /// still fit there. Otherwise every section follows the previous one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model isn't [valid](Model::validate) or if a
+    /// UV is outside of the texture.
+    pub fn to_bytes(&self) -> io::Result<(Vec<u8>, QuantizationError)> {
+        if let Some(issue) = self
+            .validate()
+            .issues
+            .into_iter()
+            .find(ModelIssue::is_error)
+        {
+            return Err(Error::new(ErrorKind::InvalidData, issue.to_string()));
+        }
+
+        let vertex_count = self.frames.first().map_or(0, |frame| frame.vertices.len());
+        let triangle_count = self.triangles.len();
+        let (width, height) = (self.texture.width(), self.texture.height());
+
+        let triangles = self.triangle_bytes()?;
+        let (frames, error) = self.frame_bytes();
+
+        let sizes = SectionSizes {
+            triangles: triangles.len(),
+            texture: width * height,
+            sequences: self
+                .sequences
+                .iter()
+                .map(|sequence| 4 * sequence.frames.len())
+                .collect(),
+            frame: frames.first().map_or(0, Vec::len),
+            frame_count: frames.len(),
+        };
+        let layout = self
+            .layout
+            .as_ref()
+            .filter(|layout| layout.fits(&sizes))
+            .cloned()
+            .unwrap_or_else(|| ModelLayout::packed(&sizes));
+
+        let mut bytes = vec![0; layout.end(&sizes)];
+        let mut write = |offset: usize, data: &[u8]| {
+            bytes[offset..offset + data.len()].copy_from_slice(data);
+        };
+
+        let mut header = vec![];
+        for value in [
+            triangle_count,
+            vertex_count,
+            width,
+            height,
+            self.frames.len(),
+            layout.frame_size,
+            self.sequences.len(),
+            layout.offset_texture,
+            layout.offset_triangles,
+            layout.offset_frames,
+            layout.offset_sequences,
+        ] {
+            header.extend((value as u32).to_le_bytes());
+        }
+        header.extend(self.locator_nodes(&layout)?);
+        write(0, &header);
+
+        write(layout.offset_triangles, &triangles);
+        write(layout.offset_texture, &self.texture.colors.concat());
+
+        for (i, (sequence, &offset)) in
+            Iterator::zip(self.sequences.iter(), &layout.sequence_offsets).enumerate()
+        {
+            let mut header = (sequence.frames.len() as u32).to_le_bytes().to_vec();
+            header.extend((offset as u32).to_le_bytes());
+            write(layout.offset_sequences + SEQUENCE_SIZE * i, &header);
+            write(
+                offset,
+                &sequence
+                    .frames
+                    .iter()
+                    .flat_map(|frame| frame.to_le_bytes())
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        for (i, (frame, data)) in Iterator::zip(self.frames.iter(), &frames).enumerate() {
+            let offset = layout.offset_frames + layout.frame_size * i;
+            write(offset, data);
+            // Padding is kept as it was parsed, it may not be all zeros.
+            if frame.padding.len() == layout.frame_size - data.len() {
+                write(offset + data.len(), &frame.padding);
+            }
+        }
+
+        Ok((bytes, error))
+    }
+
+    /// The points of every triangle.
+    fn triangle_bytes(&self) -> io::Result<Vec<u8>> {
+        let dimensions = TextureDimensions {
+            width: self.texture.width() as u32,
+            height: self.texture.height() as u32,
+        };
+
+        let mut bytes = vec![];
+        for point in self.triangles.iter().flat_map(|triangle| &triangle.points) {
+            let [u, v] = point.texel(&dimensions).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("UV ({}, {}) is outside of the texture", point.u, point.v),
+                )
+            })?;
+
+            bytes.extend(point.vertex_index.to_le_bytes());
+            bytes.extend(u.to_le_bytes());
+            bytes.extend(v.to_le_bytes());
+        }
+
+        Ok(bytes)
+    }
+
+    /// Every frame without its padding, and how far their vertices moved.
+    fn frame_bytes(&self) -> (Vec<Vec<u8>>, QuantizationError) {
+        let mut total_error = 0.0;
+        let mut max_error = 0.0f32;
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let mut bytes = vec![];
+                let errors = write_frame(&mut bytes, frame);
+                total_error += errors.iter().sum::<f32>();
+                max_error = errors.into_iter().fold(max_error, f32::max);
+                bytes
+            })
+            .collect::<Vec<_>>();
+
+        let vertices = frames.len() * self.frames.first().map_or(0, |frame| frame.vertices.len());
+        (
+            frames,
+            QuantizationError {
+                max: max_error,
+                mean: if vertices == 0 {
+                    0.0
+                } else {
+                    total_error / vertices as f32
+                },
+            },
+        )
+    }
+
+    /// The locator slots of the header, keeping the values of the slots
+    /// that aren't locators as they were parsed.
+    ///
+    /// Slots of locators that were removed keep their value too, since
+    /// there's no known value for unused slots.
+    fn locator_nodes(&self, layout: &ModelLayout) -> io::Result<[u8; 16]> {
+        let mut nodes = layout.locator_nodes;
+        for locator in &self.locators {
+            let (Some(node), Ok(vertex_index)) = (
+                nodes.get_mut(locator.slot),
+                u8::try_from(locator.vertex_index),
+            ) else {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Locator {} can't point to vertex {}",
+                        locator.slot, locator.vertex_index
+                    ),
+                ));
+            };
+            *node = vertex_index;
+        }
+
+        Ok(nodes)
+    }
+}
+
+/// Sizes of the sections of a file, in bytes.
+struct SectionSizes {
+    triangles: usize,
+    texture: usize,
+    /// Size of the frame indices of every sequence.
+    sequences: Vec<usize>,
+    /// Size of a frame without its padding.
+    frame: usize,
+    frame_count: usize,
+}
+
+/// Where the sections of a file are, and what the parser doesn't otherwise
+/// keep, so parsed models are written back byte for byte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelLayout {
+    pub offset_triangles: usize,
+    pub offset_texture: usize,
+    pub offset_sequences: usize,
+    /// Offsets of the frame indices of every sequence.
+    pub sequence_offsets: Vec<usize>,
+    pub offset_frames: usize,
+    /// Size of a frame with its padding.
+    pub frame_size: usize,
+    /// Values of every locator slot, including the ones that aren't
+    /// [locators](super::dat::locator::ModelLocator).
+    pub locator_nodes: [u8; 16],
+}
+
+impl ModelLayout {
+    /// The layout of files written from scratch, with every section right
+    /// after the previous one.
+    fn packed(sizes: &SectionSizes) -> Self {
+        let offset_triangles = HEADER_SIZE;
+        let offset_texture = offset_triangles + sizes.triangles;
+        let offset_sequences = offset_texture + sizes.texture;
+        let mut offset = offset_sequences + SEQUENCE_SIZE * sizes.sequences.len();
+        let sequence_offsets = sizes
+            .sequences
+            .iter()
+            .map(|size| {
+                offset += size;
+                offset - size
+            })
+            .collect();
+
+        Self {
+            offset_triangles,
+            offset_texture,
+            offset_sequences,
+            sequence_offsets,
+            offset_frames: offset,
+            frame_size: sizes.frame.next_multiple_of(4),
+            // Slots that aren't locators point past the last vertex, as long as
+            // there are less than 256 of them.
+            locator_nodes: [u8::MAX; 16],
+        }
+    }
+
+    /// Every section, as its offset and size.
+    fn sections(&self, sizes: &SectionSizes) -> Vec<(usize, usize)> {
+        let mut sections = vec![
+            (0, HEADER_SIZE),
+            (self.offset_triangles, sizes.triangles),
+            (self.offset_texture, sizes.texture),
+            (self.offset_sequences, SEQUENCE_SIZE * sizes.sequences.len()),
+            (self.offset_frames, self.frame_size * sizes.frame_count),
+        ];
+        sections.extend(Iterator::zip(
+            self.sequence_offsets.iter().copied(),
+            sizes.sequences.iter().copied(),
+        ));
+        sections
+    }
+
+    /// Whether sections of these sizes can be written at the same place
+    /// without overlapping.
+    fn fits(&self, sizes: &SectionSizes) -> bool {
+        let mut sections = self.sections(sizes);
+        sections.retain(|&(_, size)| size != 0);
+        sections.sort_unstable();
+
+        self.sequence_offsets.len() == sizes.sequences.len()
+            && self.frame_size >= sizes.frame
+            && sections
+                .windows(2)
+                .all(|pair| pair[0].0 + pair[0].1 <= pair[1].0)
+    }
+
+    /// Size of the file.
+    fn end(&self, sizes: &SectionSizes) -> usize {
+        self.sections(sizes)
+            .into_iter()
+            .map(|(offset, size)| offset + size)
+            .max()
+            .unwrap_or(HEADER_SIZE)
+    }
+}
+
+/// Writes a frame without its padding, returning how far each vertex moved.
+fn write_frame(bytes: &mut Vec<u8>, frame: &ModelFrame) -> Vec<f32> {
+    let (transform, radius) = transform(frame);
+
+    bytes.extend(transform.scale.iter().flat_map(|scale| scale.to_le_bytes()));
+    bytes.extend(
+        transform
+            .origin
+            .iter()
+            .flat_map(|origin| origin.to_le_bytes()),
+    );
+    bytes.extend(I24F8::saturating_from_num(radius).to_le_bytes());
+
+    let errors = frame
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let position = [vertex.x, vertex.y, vertex.z];
+            let coordinates = transform.quantize(position);
+
+            bytes.extend(coordinates);
+            bytes.push(vertex.normal_index);
+
+            let written = transform.apply(coordinates);
+            (0..3)
+                .map(|i| (written[i] - position[i]).powi(2))
+                .sum::<f32>()
+                .sqrt()
+        })
+        .collect();
+    bytes.extend(&frame.triangle_normal_indexes);
+
+    errors
+}
+
+/// The transform and the bounding sphere radius a frame is written with.
+pub(super) fn transform(frame: &ModelFrame) -> (VertexTransform, f32) {
+    let positions = frame
+        .vertices
+        .iter()
+        .map(|vertex| [vertex.x, vertex.y, vertex.z]);
+
+    if let Some(transform) = &frame.transform
+        && positions.clone().all(|position| {
+            // Bit for bit, so the frame is written exactly as it was parsed.
+            transform
+                .apply(transform.quantize(position))
+                .map(f32::to_bits)
+                == position.map(f32::to_bits)
+        })
+    {
+        return (transform.clone(), frame.bounding_sphere_radius);
+    }
+
+    (
+        VertexTransform::fit(positions),
+        ModelFrame::bounding_sphere_radius(&frame.vertices),
+    )
+}
+
+#[cfg(test)]
+#[cfg(feature = "conv")]
+mod tests {
+    use super::*;
+    use crate::asset::{Parser, model::tests::model};
+
+    #[test]
+    fn to_bytes_works() -> eyre::Result<()> {
+        let model = model();
+
+        let (bytes, error) = model.to_bytes()?;
+        assert!(error.max < 1.0 / 255.0);
+
+        let (_, parsed) = Model::parser(())(&bytes)?;
+        assert_eq!(parsed.texture, model.texture);
+        assert_eq!(parsed.sequences[0].frames, [0, 1, 0]);
+        assert_eq!(parsed.locators, model.locators);
+        let vertex = &parsed.frames[1].vertices[2];
+        assert!((vertex.y - 1.0).abs() < 1.0 / 255.0);
+        assert!((vertex.z - 1.0).abs() < 1.0 / 255.0);
+        assert_eq!(
+            parsed.triangles[0].points[1].u,
+            model.triangles[0].points[1].u
+        );
+
+        // Parsed frames keep their transform, so writing them again is exact.
+        let (rewritten, error) = parsed.to_bytes()?;
+        assert_eq!(rewritten, bytes);
+        assert_eq!(error.max, 0.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_bytes_keeps_layout() -> eyre::Result<()> {
+        let read_u32 = |bytes: &[u8], offset: usize| {
+            u32::from_le_bytes(std::array::from_fn(|i| bytes[offset + i])) as usize
+        };
+
+        let (mut bytes, _) = model().to_bytes()?;
+        // Padding that isn't zeros.
+        let (offset_frames, frame_size) = (read_u32(&bytes, 36), read_u32(&bytes, 20));
+        for frame in 0..2 {
+            bytes[offset_frames + frame_size * (frame + 1) - 3..][..3].fill(0xAB);
+        }
+        // A slot that isn't a locator.
+        bytes[44 + 7] = 0x80;
+        // The texture at the end of the file instead of after the triangles.
+        let offset_texture = read_u32(&bytes, 28);
+        let texture = bytes[offset_texture..][..4].to_vec();
+        bytes[offset_texture..][..4].fill(0);
+        let end = bytes.len() as u32;
+        bytes[28..32].copy_from_slice(&end.to_le_bytes());
+        bytes.extend(texture);
+
+        let (_, parsed) = Model::parser(())(&bytes)?;
+        let (rewritten, _) = parsed.to_bytes()?;
+        assert_eq!(rewritten, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_bytes_keeps_locators() -> eyre::Result<()> {
+        let mut model = model();
+        model.locators[0].vertex_index = 0;
+
+        let (bytes, _) = model.to_bytes()?;
+        let (_, parsed) = Model::parser(())(&bytes)?;
+        assert_eq!(parsed.locators, model.locators);
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_bytes_checks_indices() {
+        let mut model = model();
+        model.sequences[0].frames.push(2);
+
+        assert!(model.to_bytes().is_err());
+    }
+}
@@ -1,35 +1,89 @@
+use fixed::types::I16F16;
+
 use crate::{asset::Parser, utils::nom::*};
 
-// TODO(nenikitov): Should probably be a fancy utility class
-// With generics for data type and dimension
-#[derive(Debug)]
-pub struct Vec3 {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
+/// How the 8-bit vertex coordinates of a frame are turned into positions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexTransform {
+    pub scale: [I16F16; 3],
+    pub origin: [I16F16; 3],
 }
 
-impl Parser for Vec3 {
+impl Parser for VertexTransform {
     type Context<'ctx> = ();
 
     fn parser((): Self::Context<'_>) -> impl Fn(Input) -> Result<Self> {
         move |input| {
-            let (input, x) = number::le_i16f16(input)?;
-            let (input, y) = number::le_i16f16(input)?;
-            let (input, z) = number::le_i16f16(input)?;
+            let (input, scale) = multi::count!(number::le_i16f16)(input)?;
+            let (input, origin) = multi::count!(number::le_i16f16)(input)?;
 
-            Ok((
-                input,
-                Self {
-                    x: x.to_num(),
-                    y: y.to_num(),
-                    z: z.to_num(),
-                },
-            ))
+            Ok((input, Self { scale, origin }))
         }
     }
 }
 
+impl VertexTransform {
+    /// The position, in meters, of 8-bit vertex coordinates.
+    pub fn apply(&self, coordinates: [u8; 3]) -> [f32; 3] {
+        std::array::from_fn(|i| {
+            (self.scale[i].to_num::<f32>() * coordinates[i] as f32 / -256.0
+                - self.origin[i].to_num::<f32>())
+                / ModelVertex::UNITS_PER_METER
+        })
+    }
+
+    /// The 8-bit coordinates that are the closest to a position, in meters.
+    pub fn quantize(&self, position: [f32; 3]) -> [u8; 3] {
+        std::array::from_fn(|i| {
+            let scale = self.scale[i].to_num::<f32>();
+            if scale == 0.0 {
+                0
+            } else {
+                let units = position[i] * ModelVertex::UNITS_PER_METER;
+                ((units + self.origin[i].to_num::<f32>()) * -256.0 / scale)
+                    .round()
+                    .clamp(0.0, 255.0) as u8
+            }
+        })
+    }
+
+    /// The transform that spreads 8-bit coordinates evenly over the bounding
+    /// box of positions, in meters.
+    pub fn fit<I>(positions: I) -> Self
+    where
+        I: IntoIterator<Item = [f32; 3]>,
+    {
+        let (min, max) = positions.into_iter().fold(
+            ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+            |(min, max), position| {
+                (
+                    std::array::from_fn(|i| min[i].min(position[i])),
+                    std::array::from_fn(|i| max[i].max(position[i])),
+                )
+            },
+        );
+
+        // Coordinate 0 is the maximum and 255 the minimum.
+        let mut transform = Self {
+            scale: [I16F16::ZERO; 3],
+            origin: [I16F16::ZERO; 3],
+        };
+        for i in 0..3 {
+            if min[i] <= max[i] {
+                let (min, max) = (
+                    min[i] * ModelVertex::UNITS_PER_METER,
+                    max[i] * ModelVertex::UNITS_PER_METER,
+                );
+                transform.scale[i] = I16F16::saturating_from_num((max - min) * 256.0 / 255.0);
+                transform.origin[i] = I16F16::saturating_from_num(-max);
+            }
+        }
+
+        transform
+    }
+}
+
+#[derive(Clone)]
 pub struct ModelVertex {
     pub x: f32,
     pub y: f32,
@@ -39,38 +93,27 @@ pub struct ModelVertex {
 }
 
 impl ModelVertex {
-    const UNITS_PER_METER: f32 = 32.0;
-}
-
-pub struct VertexTransform {
-    scale: Vec3,
-    origin: Vec3,
+    pub const UNITS_PER_METER: f32 = 32.0;
 }
 
 impl Parser for ModelVertex {
-    type Context<'ctx> = VertexTransform;
+    type Context<'ctx> = &'ctx VertexTransform;
 
     fn parser(transform: Self::Context<'_>) -> impl Fn(Input) -> Result<Self> {
-        macro_rules! transform {
-            ($coordinate: ident) => {
-                (transform.scale.$coordinate * $coordinate as f32 / -256.0
-                    - transform.origin.$coordinate)
-                    / Self::UNITS_PER_METER
-            };
-        }
-
         move |input| {
             let (input, x) = number::le_u8(input)?;
             let (input, y) = number::le_u8(input)?;
             let (input, z) = number::le_u8(input)?;
             let (input, normal_index) = number::le_u8(input)?;
 
+            let [x, y, z] = transform.apply([x, y, z]);
+
             Ok((
                 input,
                 Self {
-                    x: transform!(x),
-                    y: transform!(y),
-                    z: transform!(z),
+                    x,
+                    y,
+                    z,
                     normal_index,
                 },
             ))
@@ -78,10 +121,41 @@ impl Parser for ModelVertex {
     }
 }
 
+#[derive(Clone)]
 pub struct ModelFrame {
     pub bounding_sphere_radius: f32,
     pub vertices: Vec<ModelVertex>,
     pub triangle_normal_indexes: Vec<u8>,
+    /// The transform the vertices were decoded with, if the frame was parsed.
+    ///
+    /// It's reused when writing the frame back, as long as the vertices still
+    /// fit it exactly.
+    pub transform: Option<VertexTransform>,
+    /// Bytes between the frame and the next one, written back as they were.
+    pub padding: Vec<u8>,
+}
+
+impl ModelFrame {
+    /// A frame that wasn't parsed, with a bounding sphere around its vertices.
+    pub fn new(vertices: Vec<ModelVertex>, triangle_normal_indexes: Vec<u8>) -> Self {
+        Self {
+            bounding_sphere_radius: Self::bounding_sphere_radius(&vertices),
+            vertices,
+            triangle_normal_indexes,
+            transform: None,
+            padding: Vec::new(),
+        }
+    }
+
+    /// Radius of the sphere around the origin of the model that contains every
+    /// vertex, in game units.
+    pub fn bounding_sphere_radius(vertices: &[ModelVertex]) -> f32 {
+        vertices
+            .iter()
+            .map(|vertex| f32::hypot(f32::hypot(vertex.x, vertex.y), vertex.z))
+            .fold(0.0, f32::max)
+            * ModelVertex::UNITS_PER_METER
+    }
 }
 
 pub struct ModelSpecs {
@@ -95,13 +169,12 @@ impl Parser for ModelFrame {
 
     fn parser(model_specs: Self::Context<'_>) -> impl Fn(Input) -> Result<Self> {
         move |input| {
-            let (input, scale) = Vec3::parser(())(input)?;
-            let (input, origin) = Vec3::parser(())(input)?;
+            let (input, transform) = VertexTransform::parser(())(input)?;
 
             let (input, bounding_sphere_radius) = number::le_i24f8(input)?;
 
             let (input, vertices) = multi::count!(
-                ModelVertex::parser(VertexTransform { scale, origin }),
+                ModelVertex::parser(&transform),
                 model_specs.vertex_count as usize
             )(input)?;
 
@@ -117,7 +190,7 @@ impl Parser for ModelFrame {
             //    - sizeof(vertices)                // sizeof(ModelVertex) * vertex_count
             //    - sizeof(triangle_normalindexes)  // sizeof(u8) triangle_count
             // ```
-            let (input, _) = bytes::take(
+            let (input, padding) = bytes::take(
                 model_specs.frame_size
                     - 28
                     - 4 * model_specs.vertex_count
@@ -130,6 +203,8 @@ impl Parser for ModelFrame {
                     bounding_sphere_radius: bounding_sphere_radius.to_num(),
                     vertices,
                     triangle_normal_indexes,
+                    transform: Some(transform),
+                    padding: padding.to_vec(),
                 },
             ))
         }
@@ -0,0 +1,40 @@
+use super::frame::ModelFrame;
+
+/// A point that follows a vertex of the model through the animation.
+///
+/// These are decoded from the `locator_nodes` of the header, where every byte
+/// is a vertex index. They most likely mark where weapons are held, where
+/// projectiles spawn, or where gibs fly from, but which slot is used for what
+/// is still unknown.
+///
+/// No value is known to mark a slot as unused, so every slot that points to a
+/// vertex is a locator, including the ones that point to vertex 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelLocator {
+    /// Position of the byte in `locator_nodes`.
+    pub slot: usize,
+    pub vertex_index: u32,
+}
+
+impl ModelLocator {
+    /// Decodes the slots that point to an existing vertex.
+    ///
+    /// Slots that point past the last vertex are skipped.
+    pub fn from_nodes(nodes: &[u8; 16], vertex_count: u32) -> Vec<Self> {
+        nodes
+            .iter()
+            .enumerate()
+            .filter(|&(_, &vertex_index)| (vertex_index as u32) < vertex_count)
+            .map(|(slot, &vertex_index)| Self {
+                slot,
+                vertex_index: vertex_index as u32,
+            })
+            .collect()
+    }
+
+    /// Position of the locator in a frame.
+    pub fn position(&self, frame: &ModelFrame) -> [f32; 3] {
+        let vertex = &frame.vertices[self.vertex_index as usize];
+        [vertex.x, vertex.y, vertex.z]
+    }
+}
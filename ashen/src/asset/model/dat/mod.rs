@@ -1,4 +1,5 @@
 pub mod frame;
 pub mod header;
+pub mod locator;
 pub mod sequence;
 pub mod triangle;
@@ -1,7 +1,11 @@
 use crate::{asset::Parser, utils::nom::*};
 
+#[derive(Clone)]
 pub struct ModelSequence {
     pub frames: Vec<u32>,
+    /// What the sequence shows, which isn't stored in the file. Sequences read
+    /// from glTF files are named after their animations.
+    pub name: Option<String>,
 }
 
 impl Parser for ModelSequence {
@@ -19,7 +23,7 @@ impl Parser for ModelSequence {
                 &full_input[offset as usize..],
             )?;
 
-            Ok((input, Self { frames }))
+            Ok((input, Self { frames, name: None }))
         }
     }
 }
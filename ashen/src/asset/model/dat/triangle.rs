@@ -1,5 +1,6 @@
 use crate::{asset::Parser, utils::nom::*};
 
+#[derive(Clone)]
 pub struct ModelPoint {
     pub vertex_index: u16,
     pub u: f32,
@@ -19,18 +20,39 @@ impl Parser for ModelPoint {
             let (input, vertex_index) = number::le_u16(input)?;
 
             let (input, u) = number::le_u16(input)?;
-            // TODO(nenikitov): Double check that this +0.5 is needed
-            let u = (u as f32 + 0.5) / texture_dimensions.width as f32;
-
             let (input, v) = number::le_u16(input)?;
-            // Y coordinates need to be flipped
-            let v = 1f32 - (v as f32 + 0.5) / texture_dimensions.height as f32;
 
-            Ok((input, Self { vertex_index, u, v }))
+            Ok((
+                input,
+                Self::from_texel(vertex_index, [u, v], texture_dimensions),
+            ))
         }
     }
 }
 
+impl ModelPoint {
+    /// A point with its UV on the center of a texel, the way points are
+    /// stored.
+    pub fn from_texel(vertex_index: u16, [u, v]: [u16; 2], texture: &TextureDimensions) -> Self {
+        // TODO(nenikitov): Double check that this +0.5 is needed
+        let u = (u as f32 + 0.5) / texture.width as f32;
+        // Y coordinates need to be flipped
+        let v = 1f32 - (v as f32 + 0.5) / texture.height as f32;
+
+        Self { vertex_index, u, v }
+    }
+
+    /// The texel the UV is the closest to, if it can be stored.
+    pub fn texel(&self, texture: &TextureDimensions) -> Option<[u16; 2]> {
+        let u = (self.u * texture.width as f32 - 0.5).round();
+        let v = ((1.0 - self.v) * texture.height as f32 - 0.5).round();
+
+        let range = 0.0..=u16::MAX as f32;
+        (range.contains(&u) && range.contains(&v)).then_some([u as u16, v as u16])
+    }
+}
+
+#[derive(Clone)]
 pub struct ModelTriangle {
     pub points: [ModelPoint; 3],
 }
@@ -0,0 +1,558 @@
+//! glTF 2.0 export.
+//!
+//! Frames are exported as morph targets of a single mesh, and sequences as
+//! animations of the morph target weights.
+
+use std::collections::HashMap;
+
+use serde_json::{Value, json};
+
+use super::{Model, ModelIssue, VertexNormals, dat::triangle::ModelPoint};
+use crate::asset::{color_map::Color, gamma_table::Gamma};
+
+const ARRAY_BUFFER: u64 = 34962;
+const ELEMENT_ARRAY_BUFFER: u64 = 34963;
+pub(super) const FLOAT: u64 = 5126;
+pub(super) const UNSIGNED_INT: u64 = 5125;
+const NEAREST: u64 = 9728;
+
+/// Binary data of the exported model, along with the views and accessors that
+/// point into it.
+#[derive(Default)]
+struct Buffer {
+    bytes: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl Buffer {
+    fn push_view(&mut self, bytes: &[u8], target: Option<u64>) -> usize {
+        // Accessors need their data to be aligned to the size of a component.
+        self.bytes.resize(self.bytes.len().next_multiple_of(4), 0);
+
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.bytes.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+
+        self.bytes.extend_from_slice(bytes);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    fn push_floats<const N: usize>(&mut self, values: &[[f32; N]], bounds: bool) -> usize {
+        let bytes = values
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_le_bytes())
+            .collect::<Vec<_>>();
+        let view = self.push_view(&bytes, (N > 1).then_some(ARRAY_BUFFER));
+
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": values.len(),
+            "type": match N {
+                1 => "SCALAR",
+                2 => "VEC2",
+                3 => "VEC3",
+                _ => "VEC4",
+            },
+        });
+        if bounds {
+            let bound = |pick: fn(f32, f32) -> f32| {
+                (0..N)
+                    .map(|i| {
+                        values
+                            .iter()
+                            .map(|value| value[i])
+                            .reduce(pick)
+                            .unwrap_or(0.0)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            accessor["min"] = json!(bound(f32::min));
+            accessor["max"] = json!(bound(f32::max));
+        }
+
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let bytes = indices
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect::<Vec<_>>();
+        let view = self.push_view(&bytes, Some(ELEMENT_ARRAY_BUFFER));
+
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+impl Model {
+    /// Builds the glTF JSON document and the binary buffer it points to.
+    fn to_gltf_parts(
+        &self,
+        palette: &[Color; 256],
+        normals: VertexNormals,
+        gamma: Option<Gamma>,
+    ) -> std::io::Result<(Value, Vec<u8>)> {
+        if let Some(issue) = self
+            .validate()
+            .issues
+            .into_iter()
+            .find(ModelIssue::is_error)
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                issue.to_string(),
+            ));
+        }
+
+        // glTF stores UVs per vertex, so vertices that are used with different
+        // UVs have to be split.
+        let mut corners = HashMap::new();
+        let mut vertices = vec![];
+        let indices = self
+            .triangles
+            .iter()
+            .flat_map(|triangle| &triangle.points)
+            .map(|point| {
+                let key = (point.vertex_index, point.u.to_bits(), point.v.to_bits());
+                *corners.entry(key).or_insert_with(|| {
+                    vertices.push(point);
+                    vertices.len() as u32 - 1
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let [positions, vertex_normals] = attributes(self, &vertices, 0, normals);
+
+        let mut buffer = Buffer::default();
+
+        let position = buffer.push_floats(&positions, true);
+        let normal = buffer.push_floats(&vertex_normals, false);
+        let texcoord = buffer.push_floats(
+            &vertices
+                .iter()
+                // UVs are flipped for Blender, glTF has its origin on the top.
+                .map(|point| [point.u, 1.0 - point.v])
+                .collect::<Vec<_>>(),
+            false,
+        );
+        let indices = buffer.push_indices(&indices);
+        let targets = targets(self, &vertices, normals, &mut buffer);
+
+        let mut png = vec![];
+        self.texture.to_png(&mut png, palette, gamma)?;
+        let image = buffer.push_view(&png, None);
+
+        let animations = animations(self, &mut buffer);
+
+        // Locators are children of the model, so they move along with it.
+        let mut nodes = vec![json!({
+            "name": "Model",
+            "mesh": 0,
+            "children": (1..=self.locators.len()).collect::<Vec<_>>(),
+        })];
+        nodes.extend(self.locators.iter().map(|locator| {
+            json!({
+                "name": format!("Locator {}", locator.slot),
+                "translation": to_y_up(locator.position(&self.frames[0])),
+            })
+        }));
+
+        let document = json!({
+            "asset": { "version": "2.0", "generator": "ashen" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": nodes,
+            "meshes": [{
+                "name": "Model",
+                "primitives": [{
+                    "attributes": {
+                        "POSITION": position,
+                        "NORMAL": normal,
+                        "TEXCOORD_0": texcoord,
+                    },
+                    "indices": indices,
+                    "material": 0,
+                    "targets": targets,
+                }],
+                "weights": (0..self.frames.len())
+                    .map(|frame| if frame == 0 { 1.0 } else { 0.0 })
+                    .collect::<Vec<_>>(),
+                "extras": {
+                    "targetNames": (0..self.frames.len())
+                        .map(|frame| format!("Frame {frame}"))
+                        .collect::<Vec<_>>(),
+                },
+            }],
+            "materials": [{
+                "name": "Material",
+                "pbrMetallicRoughness": {
+                    "baseColorTexture": { "index": 0 },
+                    "metallicFactor": 0.0,
+                    "roughnessFactor": 1.0,
+                },
+                "doubleSided": true,
+            }],
+            "textures": [{ "source": 0, "sampler": 0 }],
+            "samplers": [{ "magFilter": NEAREST, "minFilter": NEAREST }],
+            "images": [{ "bufferView": image, "mimeType": "image/png" }],
+            "animations": animations,
+            "buffers": [{ "byteLength": buffer.bytes.len() }],
+            "bufferViews": buffer.views,
+            "accessors": buffer.accessors,
+        });
+
+        Ok((document, buffer.bytes))
+    }
+
+    /// Writes the model as a `.gltf` file, with the buffer and the texture
+    /// embedded in it, vertex normals from `normals`, and `gamma` applied to
+    /// the texture.
+    ///
+    /// Every sequence becomes an animation, except sequences without frames.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model isn't [valid](Model::validate), or if
+    /// writing fails.
+    pub fn to_gltf<W>(
+        &self,
+        mut writer: W,
+        palette: &[Color; 256],
+        normals: VertexNormals,
+        gamma: Option<Gamma>,
+    ) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        let (mut document, bytes) = self.to_gltf_parts(palette, normals, gamma)?;
+        document["buffers"][0]["uri"] = json!(format!(
+            "data:application/octet-stream;base64,{}",
+            base64(&bytes)
+        ));
+
+        serde_json::to_writer_pretty(&mut writer, &document)?;
+        Ok(())
+    }
+
+    /// Writes the model as a binary `.glb` file, like [`Model::to_gltf`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model isn't [valid](Model::validate), or if
+    /// writing fails.
+    pub fn to_glb<W>(
+        &self,
+        mut writer: W,
+        palette: &[Color; 256],
+        normals: VertexNormals,
+        gamma: Option<Gamma>,
+    ) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        let (document, mut bytes) = self.to_gltf_parts(palette, normals, gamma)?;
+
+        // Chunks are padded to 4 bytes, with spaces for JSON and zeros for
+        // binary data.
+        let mut json = serde_json::to_vec(&document)?;
+        json.resize(json.len().next_multiple_of(4), b' ');
+        bytes.resize(bytes.len().next_multiple_of(4), 0);
+
+        let length = 12 + 8 + json.len() + 8 + bytes.len();
+
+        writer.write_all(b"glTF")?;
+        writer.write_all(&2u32.to_le_bytes())?;
+        writer.write_all(&(length as u32).to_le_bytes())?;
+        writer.write_all(&(json.len() as u32).to_le_bytes())?;
+        writer.write_all(b"JSON")?;
+        writer.write_all(&json)?;
+        writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        writer.write_all(b"BIN\0")?;
+        writer.write_all(&bytes)
+    }
+}
+
+/// Positions and normals of the split vertices in a frame.
+fn attributes(
+    model: &Model,
+    vertices: &[&ModelPoint],
+    frame: usize,
+    source: VertexNormals,
+) -> [Vec<[f32; 3]>; 2] {
+    let normals = model.normals(frame, source);
+
+    let positions = vertices
+        .iter()
+        .map(|point| {
+            let vertex = &model.frames[frame].vertices[point.vertex_index as usize];
+            to_y_up([vertex.x, vertex.y, vertex.z])
+        })
+        .collect();
+    let normals = vertices
+        .iter()
+        .map(|point| to_y_up(normals[point.vertex_index as usize]))
+        .collect();
+
+    [positions, normals]
+}
+
+/// One morph target per frame, relative to the first frame.
+fn targets(
+    model: &Model,
+    vertices: &[&ModelPoint],
+    normals: VertexNormals,
+    buffer: &mut Buffer,
+) -> Vec<Value> {
+    let base = attributes(model, vertices, 0, normals);
+
+    (0..model.frames.len())
+        .map(|frame| {
+            let current = attributes(model, vertices, frame, normals);
+            let [positions, normals] = std::array::from_fn(|i| {
+                Iterator::zip(current[i].iter(), &base[i])
+                    .map(|(value, base)| std::array::from_fn(|j| value[j] - base[j]))
+                    .collect::<Vec<[f32; 3]>>()
+            });
+
+            json!({
+                "POSITION": buffer.push_floats(&positions, true),
+                "NORMAL": buffer.push_floats(&normals, false),
+            })
+        })
+        .collect()
+}
+
+/// One animation of the morph target weights per sequence.
+///
+/// Sequences without frames are skipped, since glTF doesn't allow empty
+/// accessors.
+fn animations(model: &Model, buffer: &mut Buffer) -> Vec<Value> {
+    model
+        .sequences
+        .iter()
+        .enumerate()
+        .filter(|(_, sequence)| !sequence.frames.is_empty())
+        .map(|(i, sequence)| {
+            let times = (0..sequence.frames.len())
+                .map(|keyframe| [keyframe as f32 / Model::FRAMES_PER_SECOND])
+                .collect::<Vec<_>>();
+            let input = buffer.push_floats(&times, true);
+            let weights = sequence
+                .frames
+                .iter()
+                .flat_map(|&frame| {
+                    (0..model.frames.len())
+                        .map(move |target| [if target == frame as usize { 1.0 } else { 0.0 }])
+                })
+                .collect::<Vec<_>>();
+
+            let mut samplers = vec![json!({
+                "input": input,
+                "output": buffer.push_floats(&weights, false),
+                "interpolation": "LINEAR",
+            })];
+            let mut channels = vec![json!({
+                "sampler": 0,
+                "target": { "node": 0, "path": "weights" },
+            })];
+            for (j, locator) in model.locators.iter().enumerate() {
+                let translations = sequence
+                    .frames
+                    .iter()
+                    .map(|&frame| to_y_up(locator.position(&model.frames[frame as usize])))
+                    .collect::<Vec<_>>();
+
+                channels.push(json!({
+                    "sampler": samplers.len(),
+                    "target": { "node": j + 1, "path": "translation" },
+                }));
+                samplers.push(json!({
+                    "input": input,
+                    "output": buffer.push_floats(&translations, false),
+                    "interpolation": "LINEAR",
+                }));
+            }
+
+            json!({
+                "name": model.sequence_name(i),
+                "samplers": samplers,
+                "channels": channels,
+            })
+        })
+        .collect()
+}
+
+/// Converts a position from the game, which is Z up, to glTF, which is Y up.
+fn to_y_up([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [x, z, -y]
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    bytes
+        .chunks(3)
+        .flat_map(|chunk| {
+            let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+                group | (byte as u32) << (16 - 8 * i)
+            });
+
+            (0..4).map(move |i| {
+                if i <= chunk.len() {
+                    ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char
+                } else {
+                    '='
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::model::{ModelSequence, NormalTable, tests::model};
+
+    #[test]
+    fn base64_works() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn to_glb_checks_frames() {
+        let mut model = model();
+        model.frames.clear();
+
+        assert!(
+            model
+                .to_glb(
+                    vec![],
+                    &[Color { r: 0, g: 0, b: 0 }; 256],
+                    VertexNormals::Computed,
+                    None
+                )
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn to_gltf_skips_empty_sequences() -> eyre::Result<()> {
+        let palette = [Color { r: 0, g: 0, b: 0 }; 256];
+        let mut model = model();
+        model.sequences.insert(
+            0,
+            ModelSequence {
+                frames: vec![],
+                name: Some("empty".to_string()),
+            },
+        );
+
+        let (document, _) = model.to_gltf_parts(&palette, VertexNormals::Computed, None)?;
+
+        let animations = document["animations"].as_array().unwrap();
+        assert_eq!(animations.len(), 1);
+        assert_eq!(animations[0]["name"], "Sequence 1");
+        for accessor in document["accessors"].as_array().unwrap() {
+            assert_ne!(accessor["count"], 0);
+        }
+        for view in document["bufferViews"].as_array().unwrap() {
+            assert_ne!(view["byteLength"], 0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_gltf_looks_up_normals() -> eyre::Result<()> {
+        let palette = [Color { r: 0, g: 0, b: 0 }; 256];
+        let table = NormalTable::new([[1.0, 0.0, 0.0]; 256]);
+
+        // Normals come right after the positions of the 3 vertices.
+        let first_normal = |normals| -> eyre::Result<[f32; 3]> {
+            let (_, bytes) = model().to_gltf_parts(&palette, normals, None)?;
+            Ok(std::array::from_fn(|i| {
+                f32::from_le_bytes(std::array::from_fn(|j| bytes[36 + 4 * i + j]))
+            }))
+        };
+
+        assert_eq!(first_normal(VertexNormals::Computed)?, [0.0, 1.0, 0.0]);
+        assert_eq!(
+            first_normal(VertexNormals::Lookup(&table))?,
+            [1.0, 0.0, 0.0]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_glb_works() -> eyre::Result<()> {
+        let palette = [Color { r: 0, g: 0, b: 0 }; 256];
+
+        let mut glb = vec![];
+        model().to_glb(&mut glb, &palette, VertexNormals::Computed, None)?;
+
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(
+            u32::from_le_bytes(glb[8..12].try_into()?) as usize,
+            glb.len()
+        );
+
+        let json_length = u32::from_le_bytes(glb[12..16].try_into()?) as usize;
+        let document: Value = serde_json::from_slice(&glb[20..20 + json_length])?;
+
+        let primitive = &document["meshes"][0]["primitives"][0];
+        assert_eq!(primitive["targets"].as_array().map(Vec::len), Some(2));
+
+        let accessors = &document["accessors"];
+        assert_eq!(
+            accessors[primitive["attributes"]["POSITION"].as_u64().unwrap() as usize]["count"],
+            3
+        );
+        // The second frame is moved up by 1, which is +Y in glTF.
+        let target = &accessors[primitive["targets"][1]["POSITION"].as_u64().unwrap() as usize];
+        assert_eq!(target["min"], json!([0.0, 1.0, 0.0]));
+        // The triangle faces +Z, which is +Y in glTF.
+        let normals = &accessors[primitive["attributes"]["NORMAL"].as_u64().unwrap() as usize];
+        assert_eq!(normals["count"], 3);
+        assert!(primitive["targets"][1]["NORMAL"].is_u64());
+
+        // The locator follows the third vertex up in the second frame.
+        assert_eq!(document["nodes"][0]["children"], json!([1]));
+        assert_eq!(document["nodes"][1]["translation"], json!([0.0, 0.0, -1.0]));
+        let locator = &document["animations"][0]["samplers"][1];
+        let translations = &accessors[locator["output"].as_u64().unwrap() as usize];
+        assert_eq!(translations["count"], 3);
+
+        let sampler = &document["animations"][0]["samplers"][0];
+        assert_eq!(
+            accessors[sampler["input"].as_u64().unwrap() as usize]["count"],
+            3
+        );
+        assert_eq!(
+            accessors[sampler["output"].as_u64().unwrap() as usize]["count"],
+            6
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,875 @@
+//! glTF 2.0 and Wavefront OBJ import.
+//!
+//! Imported models have no locators, and their vertex and triangle normal
+//! indices are all 0, since the table they point to is unknown (see
+//! [`NormalTable`](super::NormalTable)).
+
+use std::{collections::HashMap, io};
+
+use serde_json::Value;
+
+use super::{
+    Model,
+    dat::{
+        frame::{ModelFrame, ModelVertex},
+        sequence::ModelSequence,
+        triangle::{ModelPoint, ModelTriangle, TextureDimensions},
+    },
+    gltf::{FLOAT, UNSIGNED_INT},
+};
+use crate::{
+    asset::{
+        color_map::{Color, PaletteIndex},
+        texture::Texture,
+    },
+    utils::format::read_png,
+};
+
+const UNSIGNED_BYTE: u64 = 5121;
+const UNSIGNED_SHORT: u64 = 5123;
+const TRIANGLES: u64 = 4;
+const TRIANGLE_STRIP: u64 = 5;
+const TRIANGLE_FAN: u64 = 6;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// A vertex index and a UV, in the same convention as [`ModelPoint`].
+type Corner = (usize, [f32; 2]);
+
+/// Geometry shared by every frame, before it's turned into a [`Model`].
+struct Mesh {
+    triangles: Vec<[Corner; 3]>,
+    /// Positions of every vertex in game space, for every pose.
+    poses: Vec<Vec<[f32; 3]>>,
+    /// Indices of the poses played by every sequence.
+    sequences: Vec<Vec<usize>>,
+}
+
+impl Mesh {
+    /// Builds the model, merging vertices that share every position and poses
+    /// that are the same.
+    fn into_model(self, colors: &[Vec<Color>], palette: &PaletteIndex) -> io::Result<Model> {
+        let texture = Texture {
+            colors: colors
+                .iter()
+                .map(|row| row.iter().map(|&color| palette.nearest(color)).collect())
+                .collect(),
+        };
+        let dimensions = TextureDimensions {
+            width: texture.width() as u32,
+            height: texture.height() as u32,
+        };
+
+        let vertex_count = self.poses.first().map_or(0, Vec::len);
+        let mut merged = HashMap::new();
+        let vertex_indices = (0..vertex_count)
+            .map(|vertex| {
+                let key = self
+                    .poses
+                    .iter()
+                    .map(|pose| pose[vertex].map(f32::to_bits))
+                    .collect::<Vec<_>>();
+                let count = merged.len();
+                *merged.entry(key).or_insert(count)
+            })
+            .collect::<Vec<_>>();
+        let mut kept = vec![None; merged.len()];
+        for (vertex, &index) in vertex_indices.iter().enumerate() {
+            kept[index].get_or_insert(vertex);
+        }
+        if kept.len() > u16::MAX as usize + 1 {
+            return Err(invalid(format!("{} vertices are too many", kept.len())));
+        }
+
+        let triangles = self
+            .triangles
+            .iter()
+            .map(|triangle| ModelTriangle {
+                points: triangle.map(|(vertex, uv)| {
+                    ModelPoint::from_texel(
+                        vertex_indices[vertex] as u16,
+                        texel(uv, &dimensions),
+                        &dimensions,
+                    )
+                }),
+            })
+            .collect::<Vec<_>>();
+
+        let mut frames = vec![];
+        let mut frame_indices = HashMap::new();
+        let pose_frames = self
+            .poses
+            .iter()
+            .map(|pose| {
+                let vertices = kept
+                    .iter()
+                    .flatten()
+                    .map(|&vertex| pose[vertex])
+                    .collect::<Vec<_>>();
+                let key = vertices
+                    .iter()
+                    .map(|position| position.map(f32::to_bits))
+                    .collect::<Vec<_>>();
+
+                *frame_indices.entry(key).or_insert_with(|| {
+                    frames.push(ModelFrame::new(
+                        vertices
+                            .into_iter()
+                            .map(|[x, y, z]| ModelVertex {
+                                x,
+                                y,
+                                z,
+                                normal_index: 0,
+                            })
+                            .collect(),
+                        vec![0; triangles.len()],
+                    ));
+                    frames.len() as u32 - 1
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let sequences = self
+            .sequences
+            .iter()
+            .map(|poses| ModelSequence {
+                frames: poses.iter().map(|&pose| pose_frames[pose]).collect(),
+                name: None,
+            })
+            .collect();
+
+        Ok(Model {
+            texture,
+            triangles,
+            sequences,
+            frames,
+            locators: vec![],
+            layout: None,
+        })
+    }
+}
+
+/// The texel a UV falls on, wrapping UVs that are outside of the texture.
+fn texel([u, v]: [f32; 2], texture: &TextureDimensions) -> [u16; 2] {
+    let texel = |coordinate: f32, size: u32| {
+        // The far edge belongs to the last texel, only coordinates outside of
+        // the texture wrap around.
+        let coordinate = if (0.0..=1.0).contains(&coordinate) {
+            coordinate
+        } else {
+            coordinate.rem_euclid(1.0)
+        };
+        ((coordinate * size as f32) as u32).min(size.saturating_sub(1)) as u16
+    };
+
+    // Rows go down while V goes up.
+    [texel(u, texture.width), texel(1.0 - v, texture.height)]
+}
+
+/// Converts a position from glTF and OBJ, which are Y up, to the game, which
+/// is Z up.
+fn to_z_up([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [x, -z, y]
+}
+
+/// Splits polygons, triangle strips, or triangle fans into triangles.
+fn triangulate<T: Copy>(corners: &[T], mode: u64) -> io::Result<Vec<[T; 3]>> {
+    match mode {
+        TRIANGLES => Ok(corners.as_chunks::<3>().0.to_vec()),
+        // Every other triangle of a strip is flipped to keep the winding.
+        TRIANGLE_STRIP => Ok(corners
+            .windows(3)
+            .enumerate()
+            .map(|(i, triangle)| {
+                if i % 2 == 0 {
+                    [triangle[0], triangle[1], triangle[2]]
+                } else {
+                    [triangle[1], triangle[0], triangle[2]]
+                }
+            })
+            .collect()),
+        TRIANGLE_FAN => Ok(corners
+            .windows(2)
+            .skip(1)
+            .map(|edge| [corners[0], edge[0], edge[1]])
+            .collect()),
+        mode => Err(invalid(format!(
+            "Primitive mode {mode} isn't made of triangles"
+        ))),
+    }
+}
+
+/// Vertices and triangles of an imported mesh, in game space.
+struct Geometry {
+    base: Vec<[f32; 3]>,
+    /// Displacements of every vertex, for every morph target.
+    targets: Vec<Vec<[f32; 3]>>,
+    triangles: Vec<[Corner; 3]>,
+}
+
+/// A parsed glTF file, along with the contents of its buffers.
+struct Gltf {
+    document: Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Gltf {
+    fn new(bytes: &[u8]) -> io::Result<Self> {
+        let (document, binary) = if bytes.starts_with(b"glTF") {
+            let chunk = |offset: usize| -> io::Result<(&[u8], &[u8])> {
+                let header = bytes
+                    .get(offset..offset + 8)
+                    .ok_or_else(|| invalid("GLB chunk is cut off"))?;
+                let length = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+                let data = bytes
+                    .get(offset + 8..offset + 8 + length as usize)
+                    .ok_or_else(|| invalid("GLB chunk is cut off"))?;
+                Ok((&header[4..8], data))
+            };
+
+            let (kind, json) = chunk(12)?;
+            if kind != b"JSON" {
+                return Err(invalid("GLB doesn't start with a JSON chunk"));
+            }
+            let binary = chunk(20 + json.len())
+                .ok()
+                .filter(|(kind, _)| kind == b"BIN\0")
+                .map(|(_, data)| data.to_vec());
+
+            (serde_json::from_slice::<Value>(json)?, binary)
+        } else {
+            (serde_json::from_slice::<Value>(bytes)?, None)
+        };
+
+        let mut binary = binary;
+        let buffers = document["buffers"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|buffer| match buffer["uri"].as_str() {
+                Some(uri) => data_uri(uri),
+                None => binary.take().ok_or_else(|| invalid("Buffer has no data")),
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self { document, buffers })
+    }
+
+    fn index(value: &Value) -> io::Result<usize> {
+        value
+            .as_u64()
+            .map(|index| index as usize)
+            .ok_or_else(|| invalid(format!("{value} isn't an index")))
+    }
+
+    fn view(&self, index: usize) -> io::Result<(&[u8], Option<usize>)> {
+        let view = &self.document["bufferViews"][index];
+        let buffer = self
+            .buffers
+            .get(Self::index(&view["buffer"])?)
+            .ok_or_else(|| invalid(format!("Buffer view {index} points to no buffer")))?;
+        let offset = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let length = Self::index(&view["byteLength"])?;
+
+        let bytes = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| invalid(format!("Buffer view {index} is out of bounds")))?;
+        Ok((
+            bytes,
+            view["byteStride"].as_u64().map(|stride| stride as usize),
+        ))
+    }
+
+    /// Reads every element of an accessor, with integers turned into floats.
+    fn accessor(&self, index: &Value) -> io::Result<Vec<Vec<f32>>> {
+        let index = Self::index(index)?;
+        let accessor = &self.document["accessors"][index];
+        if accessor.get("sparse").is_some() {
+            return Err(invalid(format!("Accessor {index} is sparse")));
+        }
+
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            _ => return Err(invalid(format!("Accessor {index} isn't a vector"))),
+        };
+        let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+        let (size, read): (usize, fn(&[u8]) -> f32) = match accessor["componentType"].as_u64() {
+            Some(FLOAT) => (4, |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            Some(UNSIGNED_INT) => (4, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32),
+            Some(UNSIGNED_SHORT) => (2, |b| u16::from_le_bytes([b[0], b[1]]) as f32),
+            Some(UNSIGNED_BYTE) => (1, |b| b[0] as f32),
+            _ => {
+                return Err(invalid(format!(
+                    "Accessor {index} has unsupported components"
+                )));
+            }
+        };
+        let scale = match (normalized, size) {
+            (true, 1) => u8::MAX as f32,
+            (true, 2) => u16::MAX as f32,
+            _ => 1.0,
+        };
+
+        let count = Self::index(&accessor["count"])?;
+        let (bytes, stride) = self.view(Self::index(&accessor["bufferView"])?)?;
+        let offset = accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let stride = stride.unwrap_or(size * components);
+
+        (0..count)
+            .map(|element| {
+                (0..components)
+                    .map(|component| {
+                        element
+                            .checked_mul(stride)
+                            .and_then(|start| start.checked_add(offset))
+                            .and_then(|start| start.checked_add(component * size))
+                            .and_then(|start| bytes.get(start..start.checked_add(size)?))
+                            .map(|bytes| read(bytes) / scale)
+                            .ok_or_else(|| invalid(format!("Accessor {index} is out of bounds")))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn vectors<const N: usize>(&self, index: &Value) -> io::Result<Vec<[f32; N]>> {
+        self.accessor(index)?
+            .into_iter()
+            .map(|element| {
+                element
+                    .try_into()
+                    .map_err(|_| invalid(format!("Accessor {index} doesn't have {N} components")))
+            })
+            .collect()
+    }
+
+    /// Reads the vertices and the triangles of every primitive of a mesh.
+    fn geometry(&self, primitives: &[Value], target_count: usize) -> io::Result<Geometry> {
+        let mut geometry = Geometry {
+            base: vec![],
+            targets: vec![vec![]; target_count],
+            triangles: vec![],
+        };
+
+        for primitive in primitives {
+            let offset = geometry.base.len();
+            let positions = self.vectors::<3>(&primitive["attributes"]["POSITION"])?;
+            let uvs = self.vectors::<2>(&primitive["attributes"]["TEXCOORD_0"])?;
+            let corners = match primitive.get("indices") {
+                Some(indices) => self
+                    .accessor(indices)?
+                    .into_iter()
+                    .map(|index| index[0] as usize)
+                    .collect(),
+                None => (0..positions.len()).collect::<Vec<_>>(),
+            };
+            if corners.iter().any(|&corner| corner >= positions.len()) {
+                return Err(invalid("Triangle points to a vertex that doesn't exist"));
+            }
+
+            for (i, target) in geometry.targets.iter_mut().enumerate() {
+                let displacements = self.vectors::<3>(&primitive["targets"][i]["POSITION"])?;
+                if displacements.len() != positions.len() {
+                    return Err(invalid(format!(
+                        "Morph target {i} has {} positions instead of {}",
+                        displacements.len(),
+                        positions.len()
+                    )));
+                }
+                target.extend(displacements.into_iter().map(to_z_up));
+            }
+            geometry.triangles.extend(
+                triangulate(&corners, primitive["mode"].as_u64().unwrap_or(TRIANGLES))?
+                    .into_iter()
+                    .map(|triangle| {
+                        triangle.map(|corner| {
+                            // glTF has the origin of UVs on the top.
+                            let [u, v] = uvs.get(corner).copied().unwrap_or_default();
+                            (offset + corner, [u, 1.0 - v])
+                        })
+                    }),
+            );
+            geometry.base.extend(positions.into_iter().map(to_z_up));
+        }
+
+        Ok(geometry)
+    }
+
+    /// Decodes the base color texture of a material.
+    fn texture(&self, material: &Value) -> io::Result<Vec<Vec<Color>>> {
+        let texture = &self.document["textures"]
+            [Self::index(&material["pbrMetallicRoughness"]["baseColorTexture"]["index"])?];
+        let image = &self.document["images"][Self::index(&texture["source"])?];
+
+        match image["uri"].as_str() {
+            Some(uri) => read_png(data_uri(uri)?.as_slice()),
+            None => read_png(self.view(Self::index(&image["bufferView"])?)?.0),
+        }
+    }
+}
+
+/// Decodes a base64 data URI, other URIs point to files that aren't available.
+fn data_uri(uri: &str) -> io::Result<Vec<u8>> {
+    uri.strip_prefix("data:")
+        .and_then(|data| data.split_once(";base64,"))
+        .and_then(|(_, data)| from_base64(data))
+        .ok_or_else(|| invalid("Only embedded base64 data is supported"))
+}
+
+fn from_base64(text: &str) -> Option<Vec<u8>> {
+    let sextets = text
+        .bytes()
+        .take_while(|&c| c != b'=')
+        .map(|c| match c {
+            b'A'..=b'Z' => Some(c - b'A'),
+            b'a'..=b'z' => Some(c - b'a' + 26),
+            b'0'..=b'9' => Some(c - b'0' + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(
+        sextets
+            .chunks(4)
+            .flat_map(|chunk| {
+                let group = chunk.iter().enumerate().fold(0u32, |group, (i, &sextet)| {
+                    group | (sextet as u32) << (18 - 6 * i)
+                });
+                (0..chunk.len().saturating_sub(1)).map(move |i| (group >> (16 - 8 * i)) as u8)
+            })
+            .collect(),
+    )
+}
+
+impl Model {
+    /// Reads the first mesh of a `.gltf` or `.glb` file, with the texture
+    /// matched to the closest colors of `palette`.
+    ///
+    /// Every keyframe of the animations of the morph target weights becomes a
+    /// frame, and every animation a sequence named after it, in the order
+    /// they're stored. A file without animations is read as a single sequence
+    /// that goes through every morph target. Buffers and images have to be
+    /// embedded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or if the file isn't a glTF file
+    /// with a triangle mesh that fits in a model.
+    pub fn from_gltf<R>(mut reader: R, palette: &PaletteIndex) -> io::Result<Self>
+    where
+        R: io::Read,
+    {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        let gltf = Gltf::new(&bytes)?;
+
+        let mesh = &gltf.document["meshes"][0];
+        let primitives = mesh["primitives"]
+            .as_array()
+            .filter(|primitives| !primitives.is_empty())
+            .ok_or_else(|| invalid("File has no mesh"))?;
+        let target_count = primitives[0]["targets"].as_array().map_or(0, Vec::len);
+
+        let Geometry {
+            base,
+            targets,
+            triangles,
+        } = gltf.geometry(primitives, target_count)?;
+
+        let pose = |weights: &[f32]| {
+            base.iter()
+                .enumerate()
+                .map(|(vertex, position)| {
+                    std::array::from_fn(|i| {
+                        Iterator::zip(weights.iter(), &targets)
+                            .filter(|(weight, _)| **weight != 0.0)
+                            .fold(position[i], |sum, (weight, target)| {
+                                sum + weight * target[vertex][i]
+                            })
+                    })
+                })
+                .collect::<Vec<[f32; 3]>>()
+        };
+
+        let mut poses = vec![];
+        let mut sequences = vec![];
+        let mut names = vec![];
+        for animation in gltf.document["animations"].as_array().into_iter().flatten() {
+            let Some(channel) = animation["channels"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|channel| channel["target"]["path"] == "weights")
+            else {
+                continue;
+            };
+            let sampler = &animation["samplers"][Gltf::index(&channel["sampler"])?];
+            let keyframes = gltf.accessor(&sampler["input"])?.len();
+            let weights = gltf.accessor(&sampler["output"])?.concat();
+            // Cubic splines store tangents around every value.
+            let (stride, skip) = if sampler["interpolation"] == "CUBICSPLINE" {
+                (3 * target_count, target_count)
+            } else {
+                (target_count, 0)
+            };
+
+            sequences.push(
+                (0..keyframes)
+                    .map(|keyframe| {
+                        let start = keyframe * stride + skip;
+                        let weights = weights
+                            .get(start..start + target_count)
+                            .ok_or_else(|| invalid("Animation has too few weights"))?;
+                        poses.push(pose(weights));
+                        Ok(poses.len() - 1)
+                    })
+                    .collect::<io::Result<_>>()?,
+            );
+            names.push(animation["name"].as_str().map(str::to_string));
+        }
+        if sequences.is_empty() {
+            let defaults = mesh["weights"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|weight| weight.as_f64().unwrap_or(0.0) as f32)
+                .collect::<Vec<_>>();
+            poses.push(pose(&defaults));
+            poses.extend((0..target_count).map(|target| {
+                pose(
+                    &(0..target_count)
+                        .map(|i| if i == target { 1.0 } else { 0.0 })
+                        .collect::<Vec<_>>(),
+                )
+            }));
+            sequences.push((0..poses.len()).collect());
+        }
+
+        let colors =
+            gltf.texture(&gltf.document["materials"][Gltf::index(&primitives[0]["material"])?])?;
+
+        let mut model = Mesh {
+            triangles,
+            poses,
+            sequences,
+        }
+        .into_model(&colors, palette)?;
+        for (sequence, name) in Iterator::zip(model.sequences.iter_mut(), names) {
+            sequence.name = name;
+        }
+
+        Ok(model)
+    }
+
+    /// Reads OBJ files, one per frame of every sequence, with the texture
+    /// decoded from a PNG and matched to the closest colors of `palette`.
+    ///
+    /// Triangles and UVs come from the first file, other files only need to
+    /// have the same vertices in the same order, like the ones written by
+    /// [`Model::to_obj_sequence`]. Files that are the same are stored as the
+    /// same frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, if the texture isn't a PNG, or if a
+    /// file doesn't have the vertices of the first one or doesn't fit in a
+    /// model.
+    pub fn from_obj<R, T>(
+        sequences: Vec<Vec<R>>,
+        texture: T,
+        palette: &PaletteIndex,
+    ) -> io::Result<Self>
+    where
+        R: io::Read,
+        T: io::Read,
+    {
+        let mut triangles = None;
+        let mut poses = vec![];
+        let sequences = sequences
+            .into_iter()
+            .map(|frames| {
+                frames
+                    .into_iter()
+                    .map(|reader| {
+                        let Geometry {
+                            base: positions,
+                            triangles: faces,
+                            ..
+                        } = read_obj(reader)?;
+                        if let Some(first) = poses.first().map(Vec::len)
+                            && first != positions.len()
+                        {
+                            return Err(invalid(format!(
+                                "OBJ has {} vertices instead of {first}",
+                                positions.len()
+                            )));
+                        }
+
+                        triangles.get_or_insert(faces);
+                        poses.push(positions);
+                        Ok(poses.len() - 1)
+                    })
+                    .collect::<io::Result<Vec<_>>>()
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Mesh {
+            triangles: triangles.unwrap_or_default(),
+            poses,
+            sequences,
+        }
+        .into_model(&read_png(texture)?, palette)
+    }
+}
+
+/// Reads the positions and the triangles of an OBJ file, which has no morph
+/// targets.
+fn read_obj<R>(mut reader: R) -> io::Result<Geometry>
+where
+    R: io::Read,
+{
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+
+    let mut positions = vec![];
+    let mut uvs = vec![];
+    let mut triangles = vec![];
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        let keyword = words.next();
+        let numbers = |words: std::str::SplitWhitespace| {
+            words
+                .map(|word| word.parse::<f32>().map_err(|e| invalid(e.to_string())))
+                .collect::<io::Result<Vec<_>>>()
+        };
+
+        match keyword {
+            Some("v") => {
+                let [x, y, z, ..] = numbers(words)?[..] else {
+                    return Err(invalid(format!("Vertex `{line}` has too few coordinates")));
+                };
+                positions.push(to_z_up([x, y, z]));
+            }
+            Some("vt") => {
+                let [u, v, ..] = numbers(words)?[..] else {
+                    return Err(invalid(format!("UV `{line}` has too few coordinates")));
+                };
+                uvs.push([u, v]);
+            }
+            Some("f") => {
+                // Indices start at 1, and negative ones count from the end.
+                let index = |index: Option<&str>, count: usize| {
+                    let index = index
+                        .filter(|index| !index.is_empty())
+                        .map(str::parse::<isize>)
+                        .transpose()
+                        .map_err(|e| invalid(e.to_string()))?;
+                    Ok::<_, io::Error>(index.and_then(|index| {
+                        if index < 0 {
+                            count.checked_sub(index.unsigned_abs())
+                        } else {
+                            (index as usize).checked_sub(1)
+                        }
+                    }))
+                };
+
+                let corners = words
+                    .map(|corner| {
+                        let mut indices = corner.split('/');
+                        let vertex = index(indices.next(), positions.len())?
+                            .filter(|&vertex| vertex < positions.len())
+                            .ok_or_else(|| invalid(format!("Face `{line}` has a wrong vertex")))?;
+                        let uv = index(indices.next(), uvs.len())?
+                            .and_then(|uv| uvs.get(uv).copied())
+                            .unwrap_or_default();
+                        Ok((vertex, uv))
+                    })
+                    .collect::<io::Result<Vec<_>>>()?;
+                triangles.extend(triangulate(&corners, TRIANGLE_FAN)?);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Geometry {
+        base: positions,
+        targets: vec![],
+        triangles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::{
+        color_map::ColorDistance,
+        model::{VertexNormals, tests::model},
+    };
+
+    fn palette() -> [Color; 256] {
+        std::array::from_fn(|i| Color {
+            r: i as u8,
+            g: 0,
+            b: 0,
+        })
+    }
+
+    fn check(imported: &Model, model: &Model) {
+        assert_eq!(imported.texture, model.texture);
+        assert_eq!(imported.frames.len(), 2);
+        assert_eq!(imported.sequences[0].frames, [0, 1, 0]);
+        assert_eq!(
+            imported.triangles[0].points.each_ref().map(|point| (
+                point.vertex_index,
+                point.u,
+                point.v
+            )),
+            model.triangles[0].points.each_ref().map(|point| (
+                point.vertex_index,
+                point.u,
+                point.v
+            ))
+        );
+        let vertex = &imported.frames[1].vertices[2];
+        assert_eq!([vertex.x, vertex.y, vertex.z], [0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn from_base64_works() {
+        assert_eq!(from_base64(""), Some(vec![]));
+        assert_eq!(from_base64("Zg=="), Some(b"f".to_vec()));
+        assert_eq!(from_base64("Zm8="), Some(b"fo".to_vec()));
+        assert_eq!(from_base64("Zm9vYmFy"), Some(b"foobar".to_vec()));
+        assert_eq!(from_base64("Zm9v!"), None);
+    }
+
+    #[test]
+    fn texel_works() {
+        let texture = TextureDimensions {
+            width: 4,
+            height: 2,
+        };
+
+        assert_eq!(texel([0.0, 0.0], &texture), [0, 1]);
+        assert_eq!(texel([1.0, 1.0], &texture), [3, 0]);
+        assert_eq!(texel([0.0, 1.0], &texture), [0, 0]);
+        assert_eq!(texel([1.0, 0.0], &texture), [3, 1]);
+        assert_eq!(texel([1.25, -0.25], &texture), [1, 0]);
+    }
+
+    #[test]
+    fn triangulate_works() -> eyre::Result<()> {
+        assert_eq!(
+            triangulate(&[0, 1, 2, 3], TRIANGLE_FAN)?,
+            [[0, 1, 2], [0, 2, 3]]
+        );
+        assert_eq!(
+            triangulate(&[0, 1, 2, 3], TRIANGLE_STRIP)?,
+            [[0, 1, 2], [2, 1, 3]]
+        );
+        assert!(triangulate(&[0, 1], 1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_gltf_works() -> eyre::Result<()> {
+        let model = model();
+        let palette = palette();
+        let index = PaletteIndex::new(&palette, ColorDistance::Rgb);
+
+        let mut gltf = vec![];
+        model.to_gltf(&mut gltf, &palette, VertexNormals::Computed, None)?;
+        check(&Model::from_gltf(gltf.as_slice(), &index)?, &model);
+
+        let mut model = model;
+        model.sequences[0].name = Some("fly".to_string());
+        let mut glb = vec![];
+        model.to_glb(&mut glb, &palette, VertexNormals::Computed, None)?;
+        let imported = Model::from_gltf(glb.as_slice(), &index)?;
+        check(&imported, &model);
+        assert_eq!(imported.sequence_name(0), "fly");
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_gltf_checks_offsets() -> eyre::Result<()> {
+        let palette = palette();
+        let index = PaletteIndex::new(&palette, ColorDistance::Rgb);
+
+        let mut gltf = vec![];
+        model().to_gltf(&mut gltf, &palette, VertexNormals::Computed, None)?;
+        let document: serde_json::Value = serde_json::from_slice(&gltf)?;
+
+        for (field, offset) in [
+            ("bufferViews", u64::MAX),
+            ("accessors", u64::MAX),
+            ("accessors", u64::MAX / 2),
+        ] {
+            let mut document = document.clone();
+            for value in document[field].as_array_mut().unwrap() {
+                value["byteOffset"] = offset.into();
+            }
+            let error = Model::from_gltf(serde_json::to_vec(&document)?.as_slice(), &index)
+                .err()
+                .expect("Offset is out of bounds");
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_gltf_checks_targets() -> eyre::Result<()> {
+        let palette = palette();
+        let index = PaletteIndex::new(&palette, ColorDistance::Rgb);
+
+        let mut gltf = vec![];
+        model().to_gltf(&mut gltf, &palette, VertexNormals::Computed, None)?;
+        let mut document: serde_json::Value = serde_json::from_slice(&gltf)?;
+        let target = document["meshes"][0]["primitives"][0]["targets"][1]["POSITION"].clone();
+        document["accessors"][Gltf::index(&target)?]["count"] = 2.into();
+
+        let error = Model::from_gltf(serde_json::to_vec(&document)?.as_slice(), &index)
+            .err()
+            .expect("Target is missing a position");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_obj_works() -> eyre::Result<()> {
+        let model = model();
+        let palette = palette();
+
+        let mut files = vec![vec![]; 3];
+        let mut writers = files.iter_mut();
+        model.to_obj_sequence(
+            |_| writers.next().ok_or(io::ErrorKind::UnexpectedEof.into()),
+            0,
+            "model.mtl",
+            Some(VertexNormals::Computed),
+        )?;
+        let mut png = vec![];
+        model.texture.to_png(&mut png, &palette, None)?;
+
+        let imported = Model::from_obj(
+            vec![files.iter().map(Vec::as_slice).collect()],
+            png.as_slice(),
+            &PaletteIndex::new(&palette, ColorDistance::Rgb),
+        )?;
+        check(&imported, &model);
+
+        Ok(())
+    }
+}
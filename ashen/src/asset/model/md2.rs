@@ -0,0 +1,288 @@
+//! Quake II MD2 export.
+//!
+//! MD2 stores frames the same way as the game, with 8-bit coordinates scaled
+//! by a transform of their frame, so frames that still fit the transform they
+//! were parsed with are written with the same coordinates.
+
+use std::io::{self, Error, ErrorKind, Write};
+
+use super::{Model, ModelIssue, bytes::transform, dat::triangle::TextureDimensions};
+
+const HEADER_SIZE: usize = 68;
+const SKIN_NAME_SIZE: usize = 64;
+const FRAME_NAME_SIZE: usize = 16;
+/// Size of the transform and of the name.
+const FRAME_HEADER_SIZE: usize = 40;
+
+// Limits of the original engine, which most tools still enforce.
+const MAX_VERTICES: usize = 2048;
+const MAX_TRIANGLES: usize = 4096;
+const MAX_FRAMES: usize = 512;
+const MAX_SKINS: usize = 32;
+
+impl Model {
+    /// Writes every frame of every sequence as an MD2 file.
+    ///
+    /// Frames are named after their sequence and numbered from 1 (like
+    /// `idle01`), which is how engines split MD2 frames into animations.
+    /// Frames that are played by several sequences are written once for each.
+    ///
+    /// `skins` are the paths that engines load the texture from, written
+    /// separately (with [`Texture::to_pcx`](crate::asset::texture::Texture::to_pcx)
+    /// for Quake II itself, which is where a gamma level is applied since MD2
+    /// files have no colors). Vertex normals are stored as the closest
+    /// direction of `normals`, the table of the engine the file is made for
+    /// (`anorms.h` for Quake II). It isn't included, because the meaning of the
+    /// normal indices of the game is still unknown. An empty table stores 0
+    /// for every vertex.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model isn't [valid](Model::validate), if it
+    /// goes over the limits of the format, or if writing fails.
+    pub fn to_md2<W>(&self, mut writer: W, skins: &[&str], normals: &[[f32; 3]]) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+
+        if let Some(issue) = self
+            .validate()
+            .issues
+            .into_iter()
+            .find(ModelIssue::is_error)
+        {
+            return Err(invalid(issue.to_string()));
+        }
+
+        let frames = self
+            .sequences
+            .iter()
+            .enumerate()
+            .flat_map(|(sequence, data)| {
+                let name = frame_name_prefix(&self.sequence_name(sequence));
+                data.frames
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, &frame)| (format!("{name}{:02}", i + 1), frame as usize))
+            })
+            .collect::<Vec<_>>();
+
+        let vertex_count = self.frames.first().map_or(0, |frame| frame.vertices.len());
+        let triangle_count = self.triangles.len();
+        for (what, count, max) in [
+            ("vertices", vertex_count, MAX_VERTICES),
+            ("triangles", triangle_count, MAX_TRIANGLES),
+            ("frames", frames.len(), MAX_FRAMES),
+            ("skins", skins.len(), MAX_SKINS),
+        ] {
+            if count > max {
+                return Err(invalid(format!(
+                    "MD2 can't have more than {max} {what} ({count})"
+                )));
+            }
+        }
+        if let Some(skin) = skins.iter().find(|skin| skin.len() >= SKIN_NAME_SIZE) {
+            return Err(invalid(format!("Skin path {skin} is too long")));
+        }
+
+        let (width, height) = (self.texture.width(), self.texture.height());
+        let frame_size = FRAME_HEADER_SIZE + 4 * vertex_count;
+        // Every point gets its own texture coordinates, like in OBJ exports.
+        let st_count = 3 * triangle_count;
+        // A single command that ends the list, so engines draw the triangles
+        // instead.
+        let gl_command_count = 1;
+        let offset_skins = HEADER_SIZE;
+        let offset_st = offset_skins + SKIN_NAME_SIZE * skins.len();
+        let offset_triangles = offset_st + 4 * st_count;
+        let offset_frames = offset_triangles + 12 * triangle_count;
+        let offset_gl_commands = offset_frames + frame_size * frames.len();
+        let offset_end = offset_gl_commands + 4 * gl_command_count;
+
+        let mut bytes = b"IDP2".to_vec();
+        for value in [
+            8,
+            width,
+            height,
+            frame_size,
+            skins.len(),
+            vertex_count,
+            st_count,
+            triangle_count,
+            gl_command_count,
+            frames.len(),
+            offset_skins,
+            offset_st,
+            offset_triangles,
+            offset_frames,
+            offset_gl_commands,
+            offset_end,
+        ] {
+            // Values are signed, but never big enough for it to matter.
+            bytes.extend((value as u32).to_le_bytes());
+        }
+
+        for skin in skins {
+            bytes.extend(padded_name::<SKIN_NAME_SIZE>(skin));
+        }
+
+        self.write_md2_triangles(&mut bytes)?;
+
+        for (name, frame) in &frames {
+            self.write_md2_frame(&mut bytes, *frame, name, normals);
+        }
+
+        bytes.extend(0i32.to_le_bytes());
+
+        writer.write_all(&bytes)
+    }
+
+    /// Writes the texture coordinates of every point, then the triangles.
+    fn write_md2_triangles(&self, bytes: &mut Vec<u8>) -> io::Result<()> {
+        let dimensions = TextureDimensions {
+            width: self.texture.width() as u32,
+            height: self.texture.height() as u32,
+        };
+        for point in self.triangles.iter().flat_map(|triangle| &triangle.points) {
+            let [s, t] = point
+                .texel(&dimensions)
+                .and_then(|[s, t]| Some([i16::try_from(s).ok()?, i16::try_from(t).ok()?]))
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("UV ({}, {}) is outside of the texture", point.u, point.v),
+                    )
+                })?;
+            bytes.extend(s.to_le_bytes());
+            bytes.extend(t.to_le_bytes());
+        }
+
+        for (i, triangle) in self.triangles.iter().enumerate() {
+            for point in &triangle.points {
+                bytes.extend(point.vertex_index.to_le_bytes());
+            }
+            for j in 0..3 {
+                bytes.extend(((3 * i + j) as u16).to_le_bytes());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_md2_frame(&self, bytes: &mut Vec<u8>, frame: usize, name: &str, normals: &[[f32; 3]]) {
+        let data = &self.frames[frame];
+        let (transform, _) = transform(data);
+
+        // MD2 positions are `coordinate * scale + translate`, in game units.
+        bytes.extend(
+            transform
+                .scale
+                .iter()
+                .flat_map(|scale| (scale.to_num::<f32>() / -256.0).to_le_bytes()),
+        );
+        bytes.extend(
+            transform
+                .origin
+                .iter()
+                .flat_map(|origin| (-origin.to_num::<f32>()).to_le_bytes()),
+        );
+        bytes.extend(padded_name::<FRAME_NAME_SIZE>(name));
+
+        for (vertex, normal) in Iterator::zip(data.vertices.iter(), self.vertex_normals(frame)) {
+            bytes.extend(transform.quantize([vertex.x, vertex.y, vertex.z]));
+            bytes.push(closest_normal(normals, normal));
+        }
+    }
+}
+
+/// The start of the names of the frames of a sequence, which can't end with a
+/// digit, since engines strip trailing digits to find animations.
+fn frame_name_prefix(sequence: &str) -> String {
+    let mut prefix = sequence
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        // Room for a separator, the frame number and the terminating 0.
+        .take(FRAME_NAME_SIZE - 4)
+        .collect::<String>();
+    if prefix.ends_with(|c: char| c.is_ascii_digit()) {
+        prefix.push('_');
+    }
+    prefix
+}
+
+fn padded_name<const N: usize>(name: &str) -> [u8; N] {
+    let mut bytes = [0; N];
+    for (byte, &c) in Iterator::zip(bytes.iter_mut(), name.as_bytes().iter().take(N - 1)) {
+        *byte = c;
+    }
+    bytes
+}
+
+/// Index of the direction of `normals` that is the closest to `normal`.
+fn closest_normal(normals: &[[f32; 3]], normal: [f32; 3]) -> u8 {
+    normals
+        .iter()
+        .map(|candidate| (0..3).map(|i| candidate[i] * normal[i]).sum::<f32>())
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(0, |(i, _)| i as u8)
+}
+
+#[cfg(test)]
+#[cfg(feature = "conv")]
+mod tests {
+    use super::*;
+    use crate::asset::model::{dat::frame::ModelVertex, tests::model};
+
+    fn read_i32(bytes: &[u8], offset: usize) -> i32 {
+        i32::from_le_bytes(std::array::from_fn(|i| bytes[offset + i]))
+    }
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(std::array::from_fn(|i| bytes[offset + i]))
+    }
+
+    #[test]
+    fn to_md2_works() -> eyre::Result<()> {
+        let mut model = model();
+        model.sequences[0].name = Some("fly".to_string());
+        let normals = [[0.0, 0.0, -1.0], [0.0, 0.0, 1.0]];
+
+        let mut md2 = vec![];
+        model.to_md2(&mut md2, &["models/rocket/skin.pcx"], &normals)?;
+
+        assert_eq!(md2[..4], *b"IDP2");
+        assert_eq!(read_i32(&md2, 4), 8);
+        // Vertices, triangles and frames.
+        assert_eq!(read_i32(&md2, 24), 3);
+        assert_eq!(read_i32(&md2, 32), 1);
+        assert_eq!(read_i32(&md2, 40), 3);
+        assert_eq!(read_i32(&md2, 64) as usize, md2.len());
+
+        let offset_frames = read_i32(&md2, 56) as usize;
+        let frame_size = read_i32(&md2, 16) as usize;
+        let frame = &md2[offset_frames + frame_size..][..frame_size];
+        assert_eq!(frame[24..31], *b"fly02\0\0");
+
+        // The third vertex of the second frame is 1 meter along Y and Z.
+        let vertex = &frame[40 + 2 * 4..][..4];
+        let position: [f32; 3] = std::array::from_fn(|i| {
+            vertex[i] as f32 * read_f32(frame, 4 * i) + read_f32(frame, 12 + 4 * i)
+        });
+        for (position, expected) in Iterator::zip(position.into_iter(), [0.0, 1.0, 1.0]) {
+            assert!((position - expected * ModelVertex::UNITS_PER_METER).abs() < 0.5);
+        }
+        assert_eq!(vertex[3], 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn frame_name_prefix_works() {
+        assert_eq!(frame_name_prefix("idle"), "idle");
+        assert_eq!(frame_name_prefix("Sequence 12"), "sequence12_");
+        assert_eq!(frame_name_prefix("a very long sequence"), "averylongseq");
+    }
+}
@@ -1,23 +1,48 @@
+mod bytes;
 mod dat;
+#[cfg(feature = "conv")]
+mod gltf;
+#[cfg(feature = "conv")]
+mod import;
+mod md2;
+mod normal;
+#[cfg(feature = "conv")]
+mod obj;
+mod pose;
+mod render;
+mod validate;
 
+pub use bytes::{ModelLayout, QuantizationError};
 use dat::{
     frame::{ModelFrame, ModelSpecs},
     header::ModelHeader,
+    locator::ModelLocator,
     sequence::ModelSequence,
     triangle::{ModelTriangle, TextureDimensions},
 };
+pub use normal::{NormalEstimate, NormalTable, VertexNormals};
+pub use pose::Playback;
+#[cfg(feature = "conv")]
+pub use render::AnimationFormat;
+pub use render::{Camera, Light};
+pub use validate::{FrameBounds, ModelIssue, ValidationReport};
 
 use super::{
     Parser,
+    skin::Skin,
     texture::{Texture, TextureSize},
 };
 use crate::utils::nom::*;
 
+#[derive(Clone)]
 pub struct Model {
     pub texture: Texture,
     pub triangles: Vec<ModelTriangle>,
     pub sequences: Vec<ModelSequence>,
     pub frames: Vec<ModelFrame>,
+    pub locators: Vec<ModelLocator>,
+    /// Where the sections of the file were, if the model was parsed.
+    pub layout: Option<ModelLayout>,
 }
 
 impl Parser for Model {
@@ -54,6 +79,26 @@ impl Parser for Model {
                 header.frame_count as usize
             )(&input[header.offset_frames as usize..])?;
 
+            let locators = ModelLocator::from_nodes(&header.locator_nodes, header.vertex_count);
+
+            let (_, sequence_offsets) = multi::count!(
+                |input| {
+                    let (input, _) = number::le_u32(input)?;
+                    let (input, offset) = number::le_u32(input)?;
+                    Ok((input, offset as usize))
+                },
+                header.sequence_count as usize
+            )(&input[header.offset_sequences as usize..])?;
+            let layout = ModelLayout {
+                offset_triangles: header.offset_triangles as usize,
+                offset_texture: header.offset_texture as usize,
+                offset_sequences: header.offset_sequences as usize,
+                sequence_offsets,
+                offset_frames: header.offset_frames as usize,
+                frame_size: header.frame_size as usize,
+                locator_nodes: header.locator_nodes,
+            };
+
             Ok((
                 &[],
                 Self {
@@ -61,6 +106,8 @@ impl Parser for Model {
                     triangles,
                     sequences,
                     frames,
+                    locators,
+                    layout: Some(layout),
                 },
             ))
         }
@@ -68,19 +115,41 @@ impl Parser for Model {
 }
 
 impl Model {
-    // TODO(Unavailable): Could provide conversions to gif using `shadybug`.
+    // How fast the game plays sequences is still unknown, 10 FPS is a guess
+    // that looks about right.
+    pub const FRAMES_PER_SECOND: f32 = 10.0;
+
+    /// The same model, wearing `skin` instead of its own texture.
+    #[must_use]
+    pub fn with_skin(&self, skin: &Skin) -> Self {
+        Self {
+            texture: skin.texture.clone(),
+            ..self.clone()
+        }
+    }
+
     #[cfg(feature = "conv")]
     pub fn to_blender_script<W>(
         &self,
         mut writer: W,
         palette: &[crate::asset::color_map::Color; 256],
+        gamma: Option<super::gamma_table::Gamma>,
     ) -> std::io::Result<()>
     where
         W: std::io::Write,
     {
+        use super::gamma_table::corrected_palette;
         use crate::utils::format::ModelPythonFile;
 
-        write!(writer, "{}", &self.to_py(&*palette))
+        write!(writer, "{}", self.to_py(&corrected_palette(palette, gamma)))
+    }
+
+    /// The name of a sequence, or its index if it doesn't have one.
+    pub fn sequence_name(&self, sequence: usize) -> String {
+        self.sequences[sequence]
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Sequence {sequence}"))
     }
 }
 
@@ -89,9 +158,77 @@ impl Model {
 mod tests {
     use std::{cell::LazyCell, collections::HashMap};
 
+    use dat::{frame::ModelVertex, triangle::ModelPoint};
+
     use super::*;
     use crate::{asset::color_map::ColorMap, utils::test::*};
 
+    /// A single triangle, moved up by 1 in the second frame.
+    pub(super) fn model() -> Model {
+        let frame = |offset: f32| {
+            ModelFrame::new(
+                [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+                    .map(|[x, y, z]| ModelVertex {
+                        x,
+                        y,
+                        z: z + offset,
+                        normal_index: 0,
+                    })
+                    .into(),
+                vec![0],
+            )
+        };
+        let point = |vertex_index, u, v| ModelPoint { vertex_index, u, v };
+
+        Model {
+            texture: Texture {
+                colors: vec![vec![0, 1], vec![2, 3]],
+            },
+            triangles: vec![ModelTriangle {
+                points: [
+                    point(0, 0.25, 0.75),
+                    point(1, 0.75, 0.75),
+                    point(2, 0.25, 0.25),
+                ],
+            }],
+            sequences: vec![ModelSequence {
+                frames: vec![0, 1, 0],
+                name: None,
+            }],
+            frames: vec![frame(0.0), frame(1.0)],
+            locators: vec![ModelLocator {
+                slot: 0,
+                vertex_index: 2,
+            }],
+            layout: None,
+        }
+    }
+
+    #[test]
+    fn locators_works() {
+        let mut nodes = [0xFF; 16];
+        nodes[1] = 2;
+        nodes[3] = 3;
+        nodes[5] = 0;
+
+        let locators = ModelLocator::from_nodes(&nodes, 3);
+
+        assert_eq!(
+            locators,
+            [
+                ModelLocator {
+                    slot: 1,
+                    vertex_index: 2
+                },
+                ModelLocator {
+                    slot: 5,
+                    vertex_index: 0
+                }
+            ]
+        );
+        assert_eq!(locators[0].position(&model().frames[1]), [0.0, 1.0, 1.0]);
+    }
+
     const COLOR_MAPS: LazyCell<HashMap<&str, Vec<u8>>> = LazyCell::new(|| {
         HashMap::from([
             ("creature", deflated_file!("01.dat")),
@@ -285,14 +422,268 @@ mod tests {
             (*name, color_map.shades[15])
         }));
 
+        let model_path = PARSED_PATH.join("model");
         MODELS.iter().try_for_each(|(name, palette, data)| {
             let palette = palettes.get(palette).expect("Color map is present");
             let (_, model) = Model::parser(())(data)?;
 
-            output_file(PARSED_PATH.join(format!("model/{name}.py")))
-                .and_then(|w| model.to_blender_script(w, palette))?;
+            output_file(model_path.join(format!("{name}.py")))
+                .and_then(|w| model.to_blender_script(w, palette, None))?;
+
+            output_file(model_path.join(format!("{name}.png")))
+                .and_then(|w| model.texture.to_indexed_png(w, palette, None))?;
+
+            output_file(model_path.join(format!("{name}.glb")))
+                .and_then(|w| model.to_glb(w, palette, VertexNormals::Computed, None))?;
+
+            output_file(model_path.join(format!("{name}.pcx")))
+                .and_then(|w| model.texture.to_pcx(w, palette, None))?;
+
+            output_file(model_path.join(format!("{name}.md2")))
+                .and_then(|w| model.to_md2(w, &[&format!("{name}.pcx")], &[]))?;
+
+            output_file(model_path.join(format!("{name}.mtl")))
+                .and_then(|w| model.to_mtl(w, &format!("{name}.png")))?;
+            (0..model.sequences.len()).try_for_each(|sequence| {
+                model.to_obj_sequence(
+                    |frame| {
+                        output_file(model_path.join(format!(
+                            "{name}-obj/{}/{frame}.obj",
+                            model.sequence_name(sequence)
+                        )))
+                    },
+                    sequence,
+                    &format!("../../{name}.mtl"),
+                    Some(VertexNormals::Computed),
+                )
+            })?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[ignore = "uses Ashen ROM files"]
+    fn to_bytes_matches_rom() -> eyre::Result<()> {
+        let models = MODELS;
+        models.iter().try_for_each(|(name, _, data)| {
+            let (_, model) = Model::parser(())(data)?;
+            let (bytes, error) = model.to_bytes()?;
+
+            assert_eq!(error, QuantizationError::default(), "{name}");
+            assert!(&bytes == data, "{name} is written differently");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[ignore = "uses Ashen ROM files"]
+    fn validate_rom() {
+        let models = MODELS;
+        for (name, _, data) in models.iter() {
+            let report = Model::validate_bytes(data);
+            assert!(
+                report.is_valid(),
+                "{name} is invalid:\n{}",
+                report
+                    .issues
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+    }
+
+    #[test]
+    #[ignore = "uses Ashen ROM files"]
+    fn from_glb_matches_rom() -> eyre::Result<()> {
+        use crate::asset::color_map::{ColorDistance, PaletteIndex};
+
+        let color_maps = COLOR_MAPS;
+        let models = MODELS;
+        let palettes = <HashMap<_, _>>::from_iter(color_maps.iter().map(|(name, data)| {
+            let (_, color_map) = ColorMap::parser(())(data).expect("Color map is valid");
+            (*name, color_map.shades[15])
+        }));
+
+        models.iter().try_for_each(|(name, palette, data)| {
+            let palette = palettes.get(palette).expect("Color map is present");
+            let (_, model) = Model::parser(())(data)?;
+
+            let mut glb = vec![];
+            model.to_glb(&mut glb, palette, VertexNormals::Computed, None)?;
+            let imported = Model::from_gltf(
+                glb.as_slice(),
+                &PaletteIndex::new(palette, ColorDistance::Rgb),
+            )?;
+
+            assert_eq!(imported.triangles.len(), model.triangles.len(), "{name}");
+            assert!(imported.frames.len() <= model.frames.len(), "{name}");
+            // Sequences without frames aren't exported.
+            let sequences = model
+                .sequences
+                .iter()
+                .filter(|sequence| !sequence.frames.is_empty())
+                .count();
+            assert_eq!(imported.sequences.len(), sequences, "{name}");
+
+            // Vertices are requantized over the extent of their frame, so they
+            // move by less than one 8-bit step of its widest axis.
+            let (_, error) = imported.to_bytes()?;
+            let step = imported
+                .frames
+                .iter()
+                .flat_map(|frame| {
+                    (0..3).map(|i| {
+                        let coordinates = frame.vertices.iter().map(|v| [v.x, v.y, v.z][i]);
+                        let min = coordinates.clone().fold(f32::INFINITY, f32::min);
+                        let max = coordinates.fold(f32::NEG_INFINITY, f32::max);
+                        (max - min) / 255.0
+                    })
+                })
+                .fold(0.0, f32::max);
+            assert!(
+                error.max <= step,
+                "{name}: {:.4} m max error and {:.4} m mean error, over the {step:.4} m step",
+                error.max,
+                error.mean
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[ignore = "uses Ashen ROM files"]
+    fn render_rom_previews() -> eyre::Result<()> {
+        let color_maps = COLOR_MAPS;
+        let models = MODELS;
+        let parsed_path = PARSED_PATH;
+        let color_maps = <HashMap<_, _>>::from_iter(color_maps.iter().map(|(name, data)| {
+            let (_, color_map) = ColorMap::parser(())(data).expect("Color map is valid");
+            (*name, color_map)
+        }));
+
+        models.iter().try_for_each(|(name, color_map, data)| {
+            let color_map = color_maps.get(color_map).expect("Color map is present");
+            let (_, model) = Model::parser(())(data)?;
+
+            output_file(parsed_path.join(format!("model/{name}-preview/turntable.gif"))).and_then(
+                |w| model.to_turntable(w, 0, color_map, AnimationFormat::Gif, 128, None),
+            )?;
+            (0..model.sequences.len())
+                .filter(|&sequence| !model.sequences[sequence].frames.is_empty())
+                .try_for_each(|sequence| {
+                    output_file(parsed_path.join(format!("model/{name}-preview/{sequence}.gif")))
+                        .and_then(|w| {
+                            model.to_sequence_animation(
+                                w,
+                                sequence,
+                                color_map,
+                                AnimationFormat::Gif,
+                                128,
+                                None,
+                            )
+                        })
+                })?;
 
             Ok(())
         })
     }
+
+    #[test]
+    #[ignore = "uses Ashen ROM files"]
+    fn rank_rom_palettes() -> eyre::Result<()> {
+        use crate::asset::{color_map::ColorDistance, texture::rank_palettes};
+
+        let models = MODELS;
+        let candidates = [
+            ("creature", deflated_file!("01.dat")),
+            ("creature-ghost", deflated_file!("02.dat")),
+            ("ghost-creature", deflated_file!("03.dat")),
+            ("pickup", deflated_file!("04.dat")),
+            ("pickup-ghost", deflated_file!("05.dat")),
+            ("jacob", deflated_file!("06.dat")),
+            ("level-ghost", deflated_file!("07.dat")),
+            ("player-hands", deflated_file!("08.dat")),
+            ("player-hands-ghost", deflated_file!("09.dat")),
+        ]
+        .map(|(name, data)| {
+            let (_, color_map) = ColorMap::parser(())(&data).expect("Color map is valid");
+            (name, color_map.shades[15])
+        });
+
+        // These models don't look good with the palette they're exported with.
+        let unknown = ["player-model", "pickup-key-1"];
+        let mut misranked = vec![];
+        for (name, palette, data) in models.iter() {
+            if unknown.contains(name) {
+                continue;
+            }
+            let (_, model) = Model::parser(())(data)?;
+
+            let ranking = rank_palettes(
+                &model.texture,
+                candidates
+                    .iter()
+                    .map(|(name, palette)| (*name, palette.as_slice())),
+                ColorDistance::Lab,
+            )?;
+
+            if ranking[0].0 != *palette {
+                misranked.push(format!(
+                    "{name} (uses {palette}): {}",
+                    ranking
+                        .iter()
+                        .take(3)
+                        .map(|(name, roughness)| format!("{name} ({roughness:.2})"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+        assert!(
+            misranked.is_empty(),
+            "The palette of these models doesn't rank first:\n{}",
+            misranked.join("\n")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "uses Ashen ROM files"]
+    fn estimate_rom_normals() -> eyre::Result<()> {
+        let models = MODELS;
+        let models = models
+            .iter()
+            .map(|(_, _, data)| Model::parser(())(data).map(|(_, model)| model))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let estimate = NormalEstimate::new(&models);
+
+        // If the indices point to directions, the normals computed from the
+        // geometry stay close to the estimate of their index, while unrelated
+        // directions would be around 90 degrees away from it.
+        let samples = (0..=255).map(|i| estimate.samples(i)).sum::<usize>();
+        let spread = (0..=255)
+            .map(|i| estimate.spread(i) * estimate.samples(i) as f32)
+            .sum::<f32>()
+            / samples as f32;
+        let scattered = (0..=255)
+            .filter(|&i| estimate.samples(i) > 0 && estimate.spread(i).to_degrees() > 45.0)
+            .map(|i| format!("{i} ({:.1})", estimate.spread(i).to_degrees()))
+            .collect::<Vec<_>>();
+        assert!(
+            spread.to_degrees() < 45.0,
+            "Normals are {:.1} degrees away from the estimate of their index on average, \
+             indices over 45 degrees: {}",
+            spread.to_degrees(),
+            scattered.join(", ")
+        );
+
+        Ok(())
+    }
 }
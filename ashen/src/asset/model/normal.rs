@@ -0,0 +1,252 @@
+//! Vertex and triangle normals.
+
+use super::{
+    Model,
+    dat::{
+        frame::{ModelFrame, ModelVertex},
+        triangle::ModelPoint,
+    },
+};
+
+impl Model {
+    /// Normals of every triangle of a frame, computed from its vertices.
+    pub fn triangle_normals(&self, frame: usize) -> Vec<[f32; 3]> {
+        self.triangles
+            .iter()
+            .map(|triangle| normalize(self.triangle_area_normal(frame, &triangle.points)))
+            .collect()
+    }
+
+    /// Smooth normals of every vertex of a frame, averaged from the triangles
+    /// around it.
+    pub fn vertex_normals(&self, frame: usize) -> Vec<[f32; 3]> {
+        let mut normals = vec![[0.0; 3]; self.frames[frame].vertices.len()];
+
+        for triangle in &self.triangles {
+            // Not normalized, so bigger triangles have more weight.
+            let normal = self.triangle_area_normal(frame, &triangle.points);
+            for point in &triangle.points {
+                let sum = &mut normals[point.vertex_index as usize];
+                *sum = std::array::from_fn(|i| sum[i] + normal[i]);
+            }
+        }
+
+        normals.into_iter().map(normalize).collect()
+    }
+
+    fn triangle_area_normal(&self, frame: usize, points: &[ModelPoint; 3]) -> [f32; 3] {
+        let [a, b, c] = points.each_ref().map(|point| {
+            let vertex = &self.frames[frame].vertices[point.vertex_index as usize];
+            [vertex.x, vertex.y, vertex.z]
+        });
+        let ab: [f32; 3] = std::array::from_fn(|i| b[i] - a[i]);
+        let ac: [f32; 3] = std::array::from_fn(|i| c[i] - a[i]);
+
+        [
+            ab[1] * ac[2] - ab[2] * ac[1],
+            ab[2] * ac[0] - ab[0] * ac[2],
+            ab[0] * ac[1] - ab[1] * ac[0],
+        ]
+    }
+}
+
+/// Where the normals of vertices come from.
+#[derive(Debug, Clone, Copy)]
+pub enum VertexNormals<'t> {
+    /// Averaged from the triangles around every vertex (see
+    /// [`Model::vertex_normals`]).
+    Computed,
+    /// Looked up in a table with the [normal index](ModelVertex::normal_index)
+    /// of every vertex. The table of the game isn't known, so the indices of
+    /// game models only give real normals with a table that matches it.
+    Lookup(&'t NormalTable),
+}
+
+impl Model {
+    /// Normals of every vertex of a frame.
+    pub fn normals(&self, frame: usize, source: VertexNormals) -> Vec<[f32; 3]> {
+        match source {
+            VertexNormals::Computed => self.vertex_normals(frame),
+            VertexNormals::Lookup(table) => self.frames[frame]
+                .vertices
+                .iter()
+                .map(|vertex| vertex.normal(table))
+                .collect(),
+        }
+    }
+}
+
+/// Directions that [`ModelVertex::normal_index`] and
+/// [`ModelFrame::triangle_normal_indexes`] point to.
+///
+/// The indices most likely point into a table of precomputed normals
+/// hardcoded in the executable (like the `anorms` table of Quake models). That
+/// table isn't known yet, so it has to be provided, or
+/// [estimated](NormalEstimate) from the geometry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalTable {
+    normals: [[f32; 3]; 256],
+}
+
+impl NormalTable {
+    pub fn new(normals: [[f32; 3]; 256]) -> Self {
+        Self { normals }
+    }
+
+    /// The normal of an index, in model space.
+    pub fn get(&self, index: u8) -> [f32; 3] {
+        self.normals[index as usize]
+    }
+}
+
+/// A [`NormalTable`] estimated by averaging the normals computed from the
+/// geometry of every vertex and triangle that uses each index.
+///
+/// It isn't the table of the game, only an approximation of it (and of how
+/// much each index agrees with the geometry) that helps finding it.
+pub struct NormalEstimate {
+    /// The estimated normals, with a zero vector for indices that nothing
+    /// uses.
+    pub table: NormalTable,
+    /// Mean angle (in radians) between the estimated normal and the normals
+    /// it was estimated from.
+    spread: [f32; 256],
+    samples: [usize; 256],
+}
+
+impl NormalEstimate {
+    pub fn new<'m, I>(models: I) -> Self
+    where
+        I: IntoIterator<Item = &'m Model>,
+    {
+        let samples = models
+            .into_iter()
+            .flat_map(|model| {
+                (0..model.frames.len()).flat_map(move |frame| {
+                    let vertices = Iterator::zip(
+                        model.frames[frame].vertices.iter(),
+                        model.vertex_normals(frame),
+                    )
+                    .map(|(vertex, normal)| (vertex.normal_index, normal));
+                    let triangles = Iterator::zip(
+                        model.frames[frame].triangle_normal_indexes.iter().copied(),
+                        model.triangle_normals(frame),
+                    );
+
+                    vertices.chain(triangles).collect::<Vec<_>>()
+                })
+            })
+            .filter(|(_, normal)| normal.iter().any(|&n| n != 0.0))
+            .collect::<Vec<_>>();
+
+        let mut sums = [[0.0; 3]; 256];
+        let mut counts = [0; 256];
+        for &(index, normal) in &samples {
+            let sum = &mut sums[index as usize];
+            *sum = std::array::from_fn(|i| sum[i] + normal[i]);
+            counts[index as usize] += 1;
+        }
+        let normals = sums.map(normalize);
+
+        let mut spread = [0.0; 256];
+        for &(index, normal) in &samples {
+            let estimated = normals[index as usize];
+            let cos = (0..3).map(|i| estimated[i] * normal[i]).sum::<f32>();
+            spread[index as usize] += cos.clamp(-1.0, 1.0).acos();
+        }
+        for (spread, &count) in Iterator::zip(spread.iter_mut(), &counts) {
+            *spread /= count.max(1) as f32;
+        }
+
+        Self {
+            table: NormalTable::new(normals),
+            spread,
+            samples: counts,
+        }
+    }
+
+    /// How far the normals that were used to estimate an index are from it on
+    /// average, in radians.
+    ///
+    /// Low values mean that the index reliably encodes a direction.
+    pub fn spread(&self, index: u8) -> f32 {
+        self.spread[index as usize]
+    }
+
+    /// How many vertices and triangles use an index.
+    pub fn samples(&self, index: u8) -> usize {
+        self.samples[index as usize]
+    }
+}
+
+impl ModelVertex {
+    /// The direction the normal index points to in `table`.
+    pub fn normal(&self, table: &NormalTable) -> [f32; 3] {
+        table.get(self.normal_index)
+    }
+}
+
+impl ModelFrame {
+    /// The direction the normal index of a triangle points to in `table`.
+    pub fn triangle_normal(&self, triangle: usize, table: &NormalTable) -> [f32; 3] {
+        table.get(self.triangle_normal_indexes[triangle])
+    }
+}
+
+fn normalize(vector: [f32; 3]) -> [f32; 3] {
+    let length = vector.iter().map(|n| n * n).sum::<f32>().sqrt();
+    if length == 0.0 {
+        vector
+    } else {
+        vector.map(|n| n / length)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "conv")]
+mod tests {
+    use super::*;
+    use crate::asset::model::tests::model;
+
+    #[test]
+    fn vertex_normals_works() {
+        let model = model();
+
+        assert_eq!(model.triangle_normals(0), [[0.0, 0.0, 1.0]]);
+        assert_eq!(model.vertex_normals(1), [[0.0, 0.0, 1.0]; 3]);
+    }
+
+    #[test]
+    fn normals_works() {
+        let mut model = model();
+        model.frames[1].vertices[2].normal_index = 7;
+        let mut normals = [[1.0, 0.0, 0.0]; 256];
+        normals[7] = [0.0, -1.0, 0.0];
+        let table = NormalTable::new(normals);
+
+        assert_eq!(
+            model.normals(1, VertexNormals::Lookup(&table)),
+            [[1.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, -1.0, 0.0]]
+        );
+        assert_eq!(model.frames[1].triangle_normal(0, &table), [1.0, 0.0, 0.0]);
+        assert_eq!(
+            model.normals(1, VertexNormals::Computed),
+            model.vertex_normals(1)
+        );
+    }
+
+    #[test]
+    fn estimate_works() {
+        let mut model = model();
+        model.frames[1].vertices[2].normal_index = 7;
+
+        let estimate = NormalEstimate::new([&model]);
+
+        assert_eq!(estimate.table.get(0), [0.0, 0.0, 1.0]);
+        assert_eq!(estimate.table.get(7), [0.0, 0.0, 1.0]);
+        assert_eq!(estimate.table.get(1), [0.0; 3]);
+        assert_eq!(estimate.samples(0), 7);
+        assert_eq!(estimate.samples(7), 1);
+        assert_eq!(estimate.spread(0), 0.0);
+    }
+}
@@ -0,0 +1,158 @@
+//! Wavefront OBJ export.
+
+use std::io::{self, Write};
+
+use super::{Model, VertexNormals};
+
+impl Model {
+    /// Writes a single frame as an OBJ file, using the `Material` material of
+    /// the `mtl` file (see [`Model::to_mtl`]).
+    ///
+    /// Vertex normals are optional, since some tools recompute them anyway.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn to_obj<W>(
+        &self,
+        mut writer: W,
+        frame: usize,
+        mtl: &str,
+        normals: Option<VertexNormals>,
+    ) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let vertices = &self.frames[frame].vertices;
+
+        writeln!(writer, "mtllib {mtl}")?;
+        writeln!(writer, "o Model")?;
+
+        // The game is Z up while OBJ is Y up.
+        for vertex in vertices {
+            writeln!(writer, "v {} {} {}", vertex.x, vertex.z, -vertex.y)?;
+        }
+        for point in self.triangles.iter().flat_map(|triangle| &triangle.points) {
+            writeln!(writer, "vt {} {}", point.u, point.v)?;
+        }
+        if let Some(normals) = normals {
+            for [x, y, z] in self.normals(frame, normals) {
+                writeln!(writer, "vn {x} {z} {}", -y)?;
+            }
+        }
+
+        writeln!(writer, "usemtl Material")?;
+        for (i, triangle) in self.triangles.iter().enumerate() {
+            write!(writer, "f")?;
+            for (j, point) in triangle.points.iter().enumerate() {
+                // OBJ indices start at 1.
+                let vertex = point.vertex_index as usize + 1;
+                let uv = i * 3 + j + 1;
+                if normals.is_some() {
+                    write!(writer, " {vertex}/{uv}/{vertex}")?;
+                } else {
+                    write!(writer, " {vertex}/{uv}")?;
+                }
+            }
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the material used by [`Model::to_obj`], with `texture` being
+    /// the path to the exported texture.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn to_mtl<W>(&self, mut writer: W, texture: &str) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "newmtl Material")?;
+        writeln!(writer, "Ka 1 1 1")?;
+        writeln!(writer, "Kd 1 1 1")?;
+        writeln!(writer, "Ks 0 0 0")?;
+        writeln!(writer, "d 1")?;
+        writeln!(writer, "illum 1")?;
+        writeln!(writer, "map_Kd {texture}")
+    }
+
+    /// Writes every frame of a sequence as a separate OBJ file, for tools that
+    /// play vertex animations as a series of meshes.
+    ///
+    /// `writer` is called with the position of the frame in the sequence.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `writer` or writing a frame fails.
+    pub fn to_obj_sequence<W, F>(
+        &self,
+        mut writer: F,
+        sequence: usize,
+        mtl: &str,
+        normals: Option<VertexNormals>,
+    ) -> io::Result<()>
+    where
+        W: Write,
+        F: FnMut(usize) -> io::Result<W>,
+    {
+        self.sequences[sequence]
+            .frames
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, &frame)| self.to_obj(writer(i)?, frame as usize, mtl, normals))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::model::{NormalTable, tests::model};
+
+    #[test]
+    fn to_obj_works() -> eyre::Result<()> {
+        let mut obj = vec![];
+        model().to_obj(&mut obj, 1, "model.mtl", Some(VertexNormals::Computed))?;
+        let obj = String::from_utf8(obj)?;
+
+        assert!(obj.starts_with("mtllib model.mtl\n"));
+        assert_eq!(obj.lines().filter(|line| line.starts_with("v ")).count(), 3);
+        assert!(obj.contains("\nv 0 1 -1\n"));
+        assert!(obj.contains("\nvt 0.75 0.75\n"));
+        // The triangle faces +Z, which is +Y in OBJ.
+        assert!(obj.contains("\nvn 0 1 -0\n"));
+        assert!(obj.contains("\nf 1/1/1 2/2/2 3/3/3\n"));
+
+        let table = NormalTable::new([[1.0, 0.0, 0.0]; 256]);
+        let mut obj = vec![];
+        model().to_obj(
+            &mut obj,
+            1,
+            "model.mtl",
+            Some(VertexNormals::Lookup(&table)),
+        )?;
+        assert!(String::from_utf8(obj)?.contains("\nvn 1 0 -0\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_obj_sequence_works() -> eyre::Result<()> {
+        let mut files = vec![vec![]; 3];
+        let mut writers = files.iter_mut();
+        model().to_obj_sequence(
+            |_| writers.next().ok_or(io::ErrorKind::UnexpectedEof.into()),
+            0,
+            "model.mtl",
+            None,
+        )?;
+
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0], files[2]);
+        assert_ne!(files[0], files[1]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+//! Interpolation between the frames of a sequence.
+
+use super::{
+    Model,
+    dat::{
+        frame::{ModelFrame, ModelVertex},
+        sequence::ModelSequence,
+    },
+};
+
+/// What happens when a sequence is sampled past its last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Playback {
+    /// The sequence starts over, blending the last frame into the first one.
+    Loop,
+    /// The sequence stops on its last frame.
+    Clamp,
+}
+
+impl Playback {
+    /// The frames of the sequence to blend at `time`, and how much of the
+    /// second one to use.
+    fn keyframes(self, sequence: &ModelSequence, time: f32) -> (usize, usize, f32) {
+        let count = sequence.frames.len();
+        let time = match self {
+            Playback::Loop => time.rem_euclid(count as f32),
+            Playback::Clamp => time.clamp(0.0, (count - 1) as f32),
+        };
+
+        let current = (time.floor() as usize).min(count - 1);
+        let next = match self {
+            Playback::Loop => (current + 1) % count,
+            Playback::Clamp => (current + 1).min(count - 1),
+        };
+
+        (
+            sequence.frames[current] as usize,
+            sequence.frames[next] as usize,
+            time - current as f32,
+        )
+    }
+}
+
+impl Model {
+    /// Positions of every vertex at a point of a sequence, blended linearly
+    /// between the closest frames.
+    ///
+    /// `time` is measured in frames of the sequence, so `1.5` is halfway
+    /// between its second and third frames. Multiply seconds by
+    /// [`Model::FRAMES_PER_SECOND`] to play it in real time.
+    ///
+    /// # Panics
+    ///
+    /// If the sequence has no frames.
+    pub fn pose(&self, sequence: usize, time: f32, playback: Playback) -> Vec<[f32; 3]> {
+        let (current, next, blend) = playback.keyframes(&self.sequences[sequence], time);
+
+        Iterator::zip(
+            self.frames[current].vertices.iter(),
+            &self.frames[next].vertices,
+        )
+        .map(|(a, b)| {
+            [
+                a.x + (b.x - a.x) * blend,
+                a.y + (b.y - a.y) * blend,
+                a.z + (b.z - a.z) * blend,
+            ]
+        })
+        .collect()
+    }
+
+    /// Normals of every vertex at a point of a sequence (see [`Model::pose`]),
+    /// blended between the [vertex normals](Model::vertex_normals) of the
+    /// closest frames.
+    pub fn pose_normals(&self, sequence: usize, time: f32, playback: Playback) -> Vec<[f32; 3]> {
+        let (current, next, blend) = playback.keyframes(&self.sequences[sequence], time);
+
+        Iterator::zip(
+            self.vertex_normals(current).into_iter(),
+            self.vertex_normals(next),
+        )
+        .map(|(a, b)| {
+            let normal: [f32; 3] = std::array::from_fn(|i| a[i] + (b[i] - a[i]) * blend);
+            let length = normal.iter().map(|n| n * n).sum::<f32>().sqrt();
+            if length == 0.0 {
+                normal
+            } else {
+                normal.map(|n| n / length)
+            }
+        })
+        .collect()
+    }
+
+    /// The same model, with `steps` frames for every frame of its sequences,
+    /// so it plays smoothly in exporters and engines that don't blend frames.
+    ///
+    /// Every sequence gets its own frames, even if they were shared before.
+    /// Normal indices are taken from the closest frame.
+    #[must_use]
+    pub fn resample(&self, steps: usize, playback: Playback) -> Self {
+        let mut frames = vec![];
+        let sequences = (0..self.sequences.len())
+            .map(|sequence| {
+                let count = self.sequences[sequence].frames.len();
+                let samples = match playback {
+                    _ if count == 0 => 0,
+                    Playback::Loop => count * steps,
+                    Playback::Clamp => (count - 1) * steps + 1,
+                };
+
+                ModelSequence {
+                    frames: (0..samples)
+                        .map(|sample| {
+                            let time = sample as f32 / steps as f32;
+                            let (current, next, blend) =
+                                playback.keyframes(&self.sequences[sequence], time);
+                            let closest = &self.frames[if blend < 0.5 { current } else { next }];
+
+                            let vertices = Iterator::zip(
+                                self.pose(sequence, time, playback).into_iter(),
+                                &closest.vertices,
+                            )
+                            .map(|([x, y, z], vertex)| ModelVertex {
+                                x,
+                                y,
+                                z,
+                                normal_index: vertex.normal_index,
+                            })
+                            .collect();
+
+                            frames.push(ModelFrame::new(
+                                vertices,
+                                closest.triangle_normal_indexes.clone(),
+                            ));
+                            frames.len() as u32 - 1
+                        })
+                        .collect(),
+                    name: self.sequences[sequence].name.clone(),
+                }
+            })
+            .collect();
+
+        Self {
+            sequences,
+            frames,
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "conv")]
+mod tests {
+    use super::*;
+    use crate::asset::model::tests::model;
+
+    #[test]
+    fn pose_works() {
+        let model = model();
+
+        // The sequence goes 0, 1, 0, and frame 1 is 1 higher.
+        assert_eq!(model.pose(0, 0.0, Playback::Loop)[0], [0.0, 0.0, 0.0]);
+        assert_eq!(model.pose(0, 0.25, Playback::Loop)[0], [0.0, 0.0, 0.25]);
+        assert_eq!(model.pose(0, 1.5, Playback::Loop)[0], [0.0, 0.0, 0.5]);
+        assert_eq!(model.pose(0, 4.0, Playback::Loop)[0], [0.0, 0.0, 1.0]);
+        assert_eq!(model.pose(0, -1.0, Playback::Loop)[0], [0.0, 0.0, 0.0]);
+
+        assert_eq!(model.pose(0, 2.5, Playback::Clamp)[0], [0.0, 0.0, 0.0]);
+        assert_eq!(model.pose(0, 4.0, Playback::Clamp)[0], [0.0, 0.0, 0.0]);
+        assert_eq!(model.pose(0, -1.0, Playback::Clamp)[0], [0.0, 0.0, 0.0]);
+
+        assert_eq!(
+            model.pose_normals(0, 0.5, Playback::Loop),
+            [[0.0, 0.0, 1.0]; 3]
+        );
+    }
+
+    #[test]
+    fn resample_works() {
+        let model = model();
+
+        let looped = model.resample(2, Playback::Loop);
+        assert_eq!(looped.frames.len(), 6);
+        assert_eq!(looped.sequences[0].frames, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(looped.frames[1].vertices[0].z, 0.5);
+
+        let clamped = model.resample(2, Playback::Clamp);
+        assert_eq!(clamped.frames.len(), 5);
+        assert_eq!(clamped.frames[4].vertices[0].z, 0.0);
+    }
+}
@@ -0,0 +1,536 @@
+//! CPU rasterizer, for previews and thumbnails.
+
+use std::f32::consts::{FRAC_PI_3, TAU};
+
+use super::Model;
+use crate::asset::{color_map::ColorMap, texture::RgbaBuffer};
+
+/// Closest distance to the camera that is still drawn, in meters.
+const NEAR_PLANE: f32 = 0.01;
+
+/// A camera that orbits around a point, with the Z axis up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub target: [f32; 3],
+    /// Angle around the target, in radians, where 0 looks from `-Y` towards
+    /// `+Y` and `PI / 2` looks from `+X` towards `-X`.
+    pub yaw: f32,
+    /// Angle above the horizon, in radians, which should stay between `-PI / 2`
+    /// and `PI / 2`.
+    pub pitch: f32,
+    /// Distance to the target, in meters.
+    pub distance: f32,
+    /// Vertical field of view, in radians.
+    pub field_of_view: f32,
+}
+
+impl Camera {
+    /// A camera that fits every position in view, at the given angles.
+    pub fn framing<I>(positions: I, yaw: f32, pitch: f32) -> Self
+    where
+        I: IntoIterator<Item = [f32; 3]>,
+    {
+        let positions = positions.into_iter().collect::<Vec<_>>();
+        let (min, max) = positions.iter().fold(
+            ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+            |(min, max), position| {
+                (
+                    std::array::from_fn(|i| min[i].min(position[i])),
+                    std::array::from_fn(|i| max[i].max(position[i])),
+                )
+            },
+        );
+        let target = if positions.is_empty() {
+            [0.0; 3]
+        } else {
+            std::array::from_fn(|i| f32::midpoint(min[i], max[i]))
+        };
+        let radius = positions
+            .iter()
+            .map(|position| distance(*position, target))
+            .fold(0.0, f32::max)
+            .max(NEAR_PLANE);
+
+        let field_of_view = FRAC_PI_3;
+        Self {
+            target,
+            yaw,
+            pitch,
+            // Far enough for the bounding sphere to touch the edges of the view.
+            distance: radius / (field_of_view / 2.0).sin(),
+            field_of_view,
+        }
+    }
+
+    fn eye(&self) -> [f32; 3] {
+        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
+        let (pitch_sin, pitch_cos) = self.pitch.sin_cos();
+        let direction = [pitch_cos * yaw_sin, -pitch_cos * yaw_cos, pitch_sin];
+        std::array::from_fn(|i| self.target[i] + direction[i] * self.distance)
+    }
+}
+
+/// A directional light, and how it picks shades of the color map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    /// Direction pointing towards the light.
+    pub direction: [f32; 3],
+    /// Light that surfaces facing away from the light still receive, from 0
+    /// to 1.
+    pub ambient: f32,
+    /// Shade of the color map used for surfaces that face the light.
+    pub shade: usize,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            direction: normalize([-0.5, -1.0, 1.0]),
+            ambient: 0.4,
+            shade: 15,
+        }
+    }
+}
+
+/// A point of a triangle, projected on the screen.
+#[derive(Clone, Copy)]
+struct ScreenPoint {
+    x: f32,
+    y: f32,
+    /// Inverse of the depth, which can be interpolated linearly on the screen.
+    inverse_depth: f32,
+    u: f32,
+    v: f32,
+    light: f32,
+}
+
+impl Model {
+    /// Draws a frame, with every texel shaded by picking a shade of the color
+    /// map from how much light it receives.
+    ///
+    /// Triangles are drawn from both sides, and the background is transparent.
+    /// Triangles that cross the near plane (or are behind the camera) aren't
+    /// clipped but skipped entirely, so cameras should stay outside of the
+    /// model, like the ones from [`Camera::framing`].
+    ///
+    /// To emulate a brightness setting, draw with a color map that has a gamma
+    /// level baked in (see [`GammaTable::bake`](crate::asset::gamma_table::GammaTable::bake)).
+    pub fn render(
+        &self,
+        frame: usize,
+        color_map: &ColorMap,
+        camera: &Camera,
+        light: &Light,
+        width: usize,
+        height: usize,
+    ) -> RgbaBuffer {
+        let mut buffer = RgbaBuffer {
+            width,
+            height,
+            data: vec![0; width * height * 4],
+        };
+        let mut depths = vec![0.0f32; width * height];
+
+        let eye = camera.eye();
+        let forward = normalize(std::array::from_fn(|i| camera.target[i] - eye[i]));
+        let right = normalize(cross(forward, [0.0, 0.0, 1.0]));
+        let up = cross(right, forward);
+        let focal = height as f32 / 2.0 / (camera.field_of_view / 2.0).tan();
+
+        let vertices = &self.frames[frame].vertices;
+        let normals = self.vertex_normals(frame);
+        let face_normals = self.triangle_normals(frame);
+        let light_direction = normalize(light.direction);
+
+        for (triangle, face_normal) in Iterator::zip(self.triangles.iter(), face_normals) {
+            // Surfaces seen from behind are lit as if they were facing the
+            // camera.
+            let first = &vertices[triangle.points[0].vertex_index as usize];
+            let to_eye = std::array::from_fn(|i| eye[i] - [first.x, first.y, first.z][i]);
+            let side = if dot(face_normal, to_eye) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+
+            let points = triangle.points.each_ref().map(|point| {
+                let vertex = &vertices[point.vertex_index as usize];
+                let relative = [vertex.x - eye[0], vertex.y - eye[1], vertex.z - eye[2]];
+                let depth = dot(relative, forward);
+                let normal = normals[point.vertex_index as usize];
+
+                ScreenPoint {
+                    x: width as f32 / 2.0 + dot(relative, right) / depth * focal,
+                    y: height as f32 / 2.0 - dot(relative, up) / depth * focal,
+                    inverse_depth: 1.0 / depth,
+                    u: point.u,
+                    v: point.v,
+                    light: light.ambient
+                        + (1.0 - light.ambient) * (side * dot(normal, light_direction)).max(0.0),
+                }
+            });
+            if points
+                .iter()
+                .any(|point| point.inverse_depth <= 0.0 || point.inverse_depth > 1.0 / NEAR_PLANE)
+            {
+                continue;
+            }
+
+            self.fill(&mut buffer, &mut depths, points, color_map, light.shade);
+        }
+
+        buffer
+    }
+
+    /// Fills the pixels whose centers are inside of a triangle.
+    fn fill(
+        &self,
+        buffer: &mut RgbaBuffer,
+        depths: &mut [f32],
+        [p0, p1, p2]: [ScreenPoint; 3],
+        color_map: &ColorMap,
+        shade: usize,
+    ) {
+        let edge = |p: &ScreenPoint, q: &ScreenPoint, x: f32, y: f32| {
+            (q.x - p.x) * (y - p.y) - (q.y - p.y) * (x - p.x)
+        };
+        let area = edge(&p0, &p1, p2.x, p2.y);
+        if area == 0.0 {
+            return;
+        }
+
+        let (texture_width, texture_height) = (self.texture.width(), self.texture.height());
+        let bound = |values: [f32; 3], size: usize| {
+            let min = values.into_iter().fold(f32::INFINITY, f32::min).floor();
+            let max = values.into_iter().fold(f32::NEG_INFINITY, f32::max).ceil();
+            (min.max(0.0) as usize, (max.max(0.0) as usize).min(size))
+        };
+        let (x_min, x_max) = bound([p0.x, p1.x, p2.x], buffer.width);
+        let (y_min, y_max) = bound([p0.y, p1.y, p2.y], buffer.height);
+
+        for y in y_min..y_max {
+            for x in x_min..x_max {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let weights = [
+                    edge(&p1, &p2, px, py) / area,
+                    edge(&p2, &p0, px, py) / area,
+                    edge(&p0, &p1, px, py) / area,
+                ];
+                if weights.iter().any(|&weight| weight < 0.0) {
+                    continue;
+                }
+
+                let interpolate = |value: fn(&ScreenPoint) -> f32| {
+                    weights[0] * value(&p0) + weights[1] * value(&p1) + weights[2] * value(&p2)
+                };
+                let inverse_depth = interpolate(|p| p.inverse_depth);
+                let pixel = y * buffer.width + x;
+                if inverse_depth <= depths[pixel] {
+                    continue;
+                }
+                depths[pixel] = inverse_depth;
+
+                // UVs are interpolated in 3D, so textures don't warp.
+                let u = interpolate(|p| p.u * p.inverse_depth) / inverse_depth;
+                let v = interpolate(|p| p.v * p.inverse_depth) / inverse_depth;
+                let texel_x = ((u * texture_width as f32).max(0.0) as usize).min(texture_width - 1);
+                let texel_y =
+                    (((1.0 - v) * texture_height as f32).max(0.0) as usize).min(texture_height - 1);
+                let index = self.texture.colors[texel_y][texel_x];
+
+                let shade = ((interpolate(|p| p.light) * shade as f32).round() as usize)
+                    .min(color_map.shades.len() - 1);
+                let color = color_map.shades[shade][index as usize];
+
+                buffer.data[pixel * 4..][..4].copy_from_slice(&[color.r, color.g, color.b, 255]);
+            }
+        }
+    }
+
+    /// Renders a frame from every side, going around the model once.
+    pub fn render_turntable(
+        &self,
+        frame: usize,
+        color_map: &ColorMap,
+        steps: usize,
+        size: usize,
+    ) -> Vec<RgbaBuffer> {
+        let positions = self.frames[frame]
+            .vertices
+            .iter()
+            .map(|vertex| [vertex.x, vertex.y, vertex.z]);
+
+        (0..steps)
+            .map(|step| {
+                let yaw = step as f32 / steps as f32 * TAU;
+                let camera = Camera::framing(positions.clone(), yaw, PREVIEW_PITCH);
+                self.render(frame, color_map, &camera, &Light::default(), size, size)
+            })
+            .collect()
+    }
+
+    /// Renders every frame of a sequence, from a camera that fits all of them.
+    pub fn render_sequence(
+        &self,
+        sequence: usize,
+        color_map: &ColorMap,
+        size: usize,
+    ) -> Vec<RgbaBuffer> {
+        let frames = &self.sequences[sequence].frames;
+        let camera = Camera::framing(
+            frames.iter().flat_map(|&frame| {
+                self.frames[frame as usize]
+                    .vertices
+                    .iter()
+                    .map(|vertex| [vertex.x, vertex.y, vertex.z])
+            }),
+            0.0,
+            PREVIEW_PITCH,
+        );
+
+        frames
+            .iter()
+            .map(|&frame| {
+                self.render(
+                    frame as usize,
+                    color_map,
+                    &camera,
+                    &Light::default(),
+                    size,
+                    size,
+                )
+            })
+            .collect()
+    }
+
+    /// Writes a turntable of a frame as a looping animation, with `gamma`
+    /// applied to the color map.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    #[cfg(feature = "conv")]
+    pub fn to_turntable<W>(
+        &self,
+        mut writer: W,
+        frame: usize,
+        color_map: &ColorMap,
+        format: AnimationFormat,
+        size: usize,
+        gamma: Option<crate::asset::gamma_table::Gamma>,
+    ) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        const STEPS: usize = 36;
+        const STEP_DELAY_MS: u16 = 50;
+
+        let corrected = gamma.map(|gamma| gamma.table.bake(gamma.level, color_map));
+        let color_map = corrected.as_ref().unwrap_or(color_map);
+
+        writer.write_all(&format.encode(
+            &self.render_turntable(frame, color_map, STEPS, size),
+            STEP_DELAY_MS,
+        ))
+    }
+
+    /// Writes a sequence as a looping animation, with `gamma` applied to the
+    /// color map.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sequence has no frames, or if writing fails.
+    #[cfg(feature = "conv")]
+    pub fn to_sequence_animation<W>(
+        &self,
+        mut writer: W,
+        sequence: usize,
+        color_map: &ColorMap,
+        format: AnimationFormat,
+        size: usize,
+        gamma: Option<crate::asset::gamma_table::Gamma>,
+    ) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        if self.sequences[sequence].frames.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} has no frames", self.sequence_name(sequence)),
+            ));
+        }
+
+        let corrected = gamma.map(|gamma| gamma.table.bake(gamma.level, color_map));
+        let color_map = corrected.as_ref().unwrap_or(color_map);
+
+        writer.write_all(&format.encode(
+            &self.render_sequence(sequence, color_map, size),
+            (1000.0 / Self::FRAMES_PER_SECOND) as u16,
+        ))
+    }
+}
+
+/// Angle above the horizon of previews, in radians.
+const PREVIEW_PITCH: f32 = 0.35;
+
+#[cfg(feature = "conv")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+}
+
+#[cfg(feature = "conv")]
+impl AnimationFormat {
+    fn encode(self, frames: &[RgbaBuffer], frame_delay_ms: u16) -> Vec<u8> {
+        use crate::utils::format::RgbaAnimationFile;
+
+        match self {
+            AnimationFormat::Gif => frames.to_gif(frame_delay_ms),
+            AnimationFormat::Apng => frames.to_apng(frame_delay_ms),
+        }
+    }
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|i| a[i] * b[i]).sum()
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f32>().sqrt()
+}
+
+fn normalize(vector: [f32; 3]) -> [f32; 3] {
+    let length = dot(vector, vector).sqrt();
+    vector.map(|n| n / length)
+}
+
+#[cfg(test)]
+#[cfg(feature = "conv")]
+mod tests {
+    use super::*;
+    use crate::asset::{
+        Parser,
+        color_map::Color,
+        gamma_table::{Gamma, GammaTable},
+        model::tests::model,
+    };
+
+    fn color_map() -> ColorMap {
+        ColorMap {
+            shades: Box::new(std::array::from_fn(|shade| {
+                std::array::from_fn(|i| Color {
+                    r: shade as u8,
+                    g: i as u8,
+                    b: 0,
+                })
+            })),
+        }
+    }
+
+    #[test]
+    fn render_works() {
+        let model = model();
+
+        // Looking straight down at the triangle.
+        let camera = Camera {
+            target: [0.25, 0.25, 0.0],
+            yaw: 0.0,
+            pitch: 1.5,
+            distance: 2.0,
+            field_of_view: FRAC_PI_3,
+        };
+        let light = Light {
+            direction: [0.0, 0.0, 1.0],
+            ambient: 0.5,
+            shade: 20,
+        };
+        let buffer = model.render(0, &color_map(), &camera, &light, 16, 16);
+
+        assert_eq!((buffer.width, buffer.height), (16, 16));
+        // The middle of the triangle is lit fully, and the corners are empty.
+        let [r, _, _, a] = buffer.pixel(8, 8);
+        assert_eq!((r, a), (20, 255));
+        assert_eq!(buffer.pixel(0, 0)[3], 0);
+        assert_eq!(buffer.pixel(15, 0)[3], 0);
+
+        // Seen from below, the triangle is still drawn.
+        let camera = Camera {
+            pitch: -1.5,
+            ..camera
+        };
+        let buffer = model.render(0, &color_map(), &camera, &light, 16, 16);
+        assert_eq!(buffer.pixel(8, 8)[3], 255);
+    }
+
+    #[test]
+    fn framing_works() {
+        let camera = Camera::framing([[0.0, 0.0, 0.0], [2.0, 0.0, 0.0]], 0.0, 0.0);
+
+        assert_eq!(camera.target, [1.0, 0.0, 0.0]);
+        assert!((camera.distance - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn to_turntable_works() -> eyre::Result<()> {
+        let model = model();
+
+        let mut gif = vec![];
+        model.to_turntable(&mut gif, 0, &color_map(), AnimationFormat::Gif, 8, None)?;
+        assert!(gif.starts_with(b"GIF89a"));
+
+        let mut apng = vec![];
+        model.to_sequence_animation(&mut apng, 0, &color_map(), AnimationFormat::Apng, 8, None)?;
+        assert!(apng.starts_with(b"\x89PNG"));
+        assert!(apng.windows(4).any(|chunk| chunk == b"acTL"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_turntable_applies_gamma() -> eyre::Result<()> {
+        let model = model();
+        // Every level turns colors black.
+        let (_, table) = GammaTable::parser(())(&vec![0; 256 * 101])?;
+
+        let mut apng = vec![];
+        model.to_turntable(&mut apng, 0, &color_map(), AnimationFormat::Apng, 8, None)?;
+        let mut dark = vec![];
+        model.to_turntable(
+            &mut dark,
+            0,
+            &color_map(),
+            AnimationFormat::Apng,
+            8,
+            Some(Gamma {
+                table: &table,
+                level: 50,
+            }),
+        )?;
+        assert_ne!(apng, dark);
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_sequence_animation_checks_frames() {
+        let mut model = model();
+        model.sequences[0].frames.clear();
+
+        for format in [AnimationFormat::Gif, AnimationFormat::Apng] {
+            assert!(
+                model
+                    .to_sequence_animation(vec![], 0, &color_map(), format, 8, None)
+                    .is_err()
+            );
+        }
+    }
+}
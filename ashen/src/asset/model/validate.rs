@@ -0,0 +1,496 @@
+//! Checks for models that weren't made by the game, like mods.
+
+use std::fmt::{self, Display};
+
+use super::{
+    Model,
+    bytes::{FRAME_HEADER_SIZE, HEADER_SIZE, SEQUENCE_SIZE, TRIANGLE_SIZE},
+    dat::{frame::ModelFrame, header::ModelHeader, triangle::TextureDimensions},
+};
+use crate::{asset::Parser, utils::nom::Input};
+
+/// A problem found by [`Model::validate`] or [`Model::validate_bytes`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelIssue {
+    /// A part of the file is past its end.
+    Section {
+        name: &'static str,
+        offset: usize,
+        size: usize,
+        file_size: usize,
+    },
+    /// Frames are too small to hold their vertices and triangle normals.
+    FrameSize { frame_size: usize, minimum: usize },
+    /// The texture is empty, or its rows aren't all as wide.
+    Texture { width: usize, height: usize },
+    /// There are no frames, so there are no vertices either.
+    NoFrames,
+    /// A triangle uses a vertex that doesn't exist.
+    VertexIndex {
+        triangle: usize,
+        vertex_index: u16,
+        vertex_count: usize,
+    },
+    /// A UV is outside of the texture, so it wraps around.
+    Uv { triangle: usize, uv: [f32; 2] },
+    /// A sequence plays a frame that doesn't exist.
+    FrameIndex {
+        sequence: usize,
+        frame: u32,
+        frame_count: usize,
+    },
+    /// A frame doesn't have as many vertices or triangles as the first one.
+    FrameShape {
+        frame: usize,
+        vertex_count: usize,
+        triangle_count: usize,
+    },
+    /// A locator points to a vertex that doesn't exist, or can't be stored.
+    Locator { slot: usize, vertex_index: u32 },
+    /// The file couldn't be parsed for another reason.
+    Parse,
+    /// Vertices of a frame are outside of its stored bounding sphere.
+    BoundingSphere {
+        frame: usize,
+        stored: f32,
+        computed: f32,
+    },
+}
+
+impl ModelIssue {
+    /// Whether the model can't be used or written with this issue, as opposed
+    /// to only looking wrong.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Self::Uv { .. } | Self::BoundingSphere { .. })
+    }
+}
+
+impl Display for ModelIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Section {
+                name,
+                offset,
+                size,
+                file_size,
+            } => write!(
+                f,
+                "{name} ({size} bytes at {offset}) is past the end of the file ({file_size} bytes)"
+            ),
+            Self::FrameSize {
+                frame_size,
+                minimum,
+            } => write!(
+                f,
+                "Frames are {frame_size} bytes, but need at least {minimum}"
+            ),
+            Self::Texture { width, height } => {
+                write!(f, "Texture of {width}x{height} isn't a valid image")
+            }
+            Self::NoFrames => write!(f, "Model has no frames"),
+            Self::VertexIndex {
+                triangle,
+                vertex_index,
+                vertex_count,
+            } => write!(
+                f,
+                "Triangle {triangle} uses vertex {vertex_index}, but there are {vertex_count}"
+            ),
+            Self::Uv { triangle, uv } => write!(
+                f,
+                "Triangle {triangle} has UV ({}, {}) outside of the texture",
+                uv[0], uv[1]
+            ),
+            Self::FrameIndex {
+                sequence,
+                frame,
+                frame_count,
+            } => write!(
+                f,
+                "Sequence {sequence} plays frame {frame}, but there are {frame_count}"
+            ),
+            Self::FrameShape {
+                frame,
+                vertex_count,
+                triangle_count,
+            } => write!(
+                f,
+                "Frame {frame} has {vertex_count} vertices and {triangle_count} triangles, unlike frame 0"
+            ),
+            Self::Locator { slot, vertex_index } => {
+                write!(f, "Locator {slot} can't point to vertex {vertex_index}")
+            }
+            Self::Parse => write!(f, "File couldn't be parsed"),
+            Self::BoundingSphere {
+                frame,
+                stored,
+                computed,
+            } => write!(
+                f,
+                "Frame {frame} has a bounding sphere of {stored}, but its vertices reach {computed}"
+            ),
+        }
+    }
+}
+
+/// Extent of a frame, recomputed from its vertices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameBounds {
+    /// Corners of the bounding box, in meters.
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    /// Radius of the bounding sphere around the origin, in game units.
+    pub radius: f32,
+    /// Radius of the bounding sphere stored in the frame, in game units.
+    pub stored_radius: f32,
+}
+
+/// Everything [`Model::validate`] found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<ModelIssue>,
+    /// Bounds of every frame, if the frames could be read.
+    pub frames: Vec<FrameBounds>,
+}
+
+impl ValidationReport {
+    /// Whether the model can be used and written, though it may still have
+    /// issues that only make it look wrong.
+    pub fn is_valid(&self) -> bool {
+        !self.issues.iter().any(ModelIssue::is_error)
+    }
+}
+
+impl Model {
+    /// Checks that the parts of the model agree with each other, and
+    /// recomputes the bounds of every frame.
+    pub fn validate(&self) -> ValidationReport {
+        let mut issues = vec![];
+
+        let (width, height) = (
+            self.texture.colors.first().map_or(0, Vec::len),
+            self.texture.colors.len(),
+        );
+        if width == 0 || self.texture.colors.iter().any(|row| row.len() != width) {
+            issues.push(ModelIssue::Texture { width, height });
+        }
+
+        if self.frames.is_empty() {
+            issues.push(ModelIssue::NoFrames);
+        }
+        let vertex_count = self.frames.first().map_or(0, |frame| frame.vertices.len());
+        for (triangle, points) in self
+            .triangles
+            .iter()
+            .map(|triangle| &triangle.points)
+            .enumerate()
+        {
+            if let Some(point) = points
+                .iter()
+                .find(|point| point.vertex_index as usize >= vertex_count)
+            {
+                issues.push(ModelIssue::VertexIndex {
+                    triangle,
+                    vertex_index: point.vertex_index,
+                    vertex_count,
+                });
+            }
+            if let Some(point) = points
+                .iter()
+                .find(|point| !(0.0..=1.0).contains(&point.u) || !(0.0..=1.0).contains(&point.v))
+            {
+                issues.push(ModelIssue::Uv {
+                    triangle,
+                    uv: [point.u, point.v],
+                });
+            }
+        }
+
+        for (sequence, frames) in self
+            .sequences
+            .iter()
+            .map(|sequence| &sequence.frames)
+            .enumerate()
+        {
+            if let Some(&frame) = frames
+                .iter()
+                .find(|&&frame| frame as usize >= self.frames.len())
+            {
+                issues.push(ModelIssue::FrameIndex {
+                    sequence,
+                    frame,
+                    frame_count: self.frames.len(),
+                });
+            }
+        }
+
+        issues.extend(
+            self.frames
+                .iter()
+                .enumerate()
+                .filter(|(_, frame)| {
+                    frame.vertices.len() != vertex_count
+                        || frame.triangle_normal_indexes.len() != self.triangles.len()
+                })
+                .map(|(frame, data)| ModelIssue::FrameShape {
+                    frame,
+                    vertex_count: data.vertices.len(),
+                    triangle_count: data.triangle_normal_indexes.len(),
+                }),
+        );
+
+        issues.extend(
+            self.locators
+                .iter()
+                .filter(|locator| {
+                    locator.slot >= 16 || locator.vertex_index as usize >= vertex_count.min(256)
+                })
+                .map(|locator| ModelIssue::Locator {
+                    slot: locator.slot,
+                    vertex_index: locator.vertex_index,
+                }),
+        );
+
+        let frames = self.frame_bounds();
+        for (frame, (data, bounds)) in Iterator::zip(self.frames.iter(), &frames).enumerate() {
+            // Stored radii have 8 fractional bits, and vertices can be rounded
+            // by up to a step of their transform.
+            let tolerance = data.transform.as_ref().map_or(0.0, |transform| {
+                transform
+                    .scale
+                    .iter()
+                    .map(|scale| scale.abs().to_num::<f32>() / 256.0)
+                    .fold(0.0, f32::max)
+            }) + 1.0 / 256.0;
+            if bounds.radius > bounds.stored_radius + tolerance {
+                issues.push(ModelIssue::BoundingSphere {
+                    frame,
+                    stored: bounds.stored_radius,
+                    computed: bounds.radius,
+                });
+            }
+        }
+
+        ValidationReport { issues, frames }
+    }
+
+    /// Checks a file before parsing it, so that broken files are reported
+    /// instead of making the parser panic, then [validates](Model::validate)
+    /// the model it contains.
+    pub fn validate_bytes(input: Input) -> ValidationReport {
+        let section = |name, offset: usize, size: usize| {
+            (offset.saturating_add(size) > input.len()).then_some(ModelIssue::Section {
+                name,
+                offset,
+                size,
+                file_size: input.len(),
+            })
+        };
+
+        let Ok((_, header)) = ModelHeader::parser(())(input) else {
+            return ValidationReport {
+                issues: section("Header", 0, HEADER_SIZE).into_iter().collect(),
+                frames: vec![],
+            };
+        };
+
+        let mut issues = header_issues(&header);
+        let [width, height, frame_size] = [
+            header.texture_width,
+            header.texture_height,
+            header.frame_size,
+        ]
+        .map(|n| n as usize);
+        issues.extend(
+            [
+                section(
+                    "Triangles",
+                    header.offset_triangles as usize,
+                    TRIANGLE_SIZE.saturating_mul(header.triangle_count as usize),
+                ),
+                section(
+                    "Texture",
+                    header.offset_texture as usize,
+                    width.saturating_mul(height),
+                ),
+                section(
+                    "Sequences",
+                    header.offset_sequences as usize,
+                    SEQUENCE_SIZE.saturating_mul(header.sequence_count as usize),
+                ),
+                section(
+                    "Frames",
+                    header.offset_frames as usize,
+                    frame_size.saturating_mul(header.frame_count as usize),
+                ),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+        if !issues.is_empty() {
+            return ValidationReport {
+                issues,
+                frames: vec![],
+            };
+        }
+
+        // The tables of frames of sequences are anywhere in the file.
+        issues.extend(
+            input[header.offset_sequences as usize..]
+                .as_chunks::<SEQUENCE_SIZE>()
+                .0
+                .iter()
+                .take(header.sequence_count as usize)
+                .filter_map(|sequence| {
+                    let [count, offset] = [0, 4].map(|start| {
+                        u32::from_le_bytes(std::array::from_fn(|i| sequence[start + i])) as usize
+                    });
+                    section("Frames of a sequence", offset, 4usize.saturating_mul(count))
+                }),
+        );
+        if !issues.is_empty() {
+            return ValidationReport {
+                issues,
+                frames: vec![],
+            };
+        }
+
+        match Model::parser(())(input) {
+            Ok((_, model)) => model.validate(),
+            Err(_) => ValidationReport {
+                issues: vec![ModelIssue::Parse],
+                frames: vec![],
+            },
+        }
+    }
+
+    fn frame_bounds(&self) -> Vec<FrameBounds> {
+        self.frames
+            .iter()
+            .map(|frame| {
+                let (min, max) = frame.vertices.iter().fold(
+                    ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+                    |(min, max), vertex| {
+                        let position = [vertex.x, vertex.y, vertex.z];
+                        (
+                            std::array::from_fn(|i| min[i].min(position[i])),
+                            std::array::from_fn(|i| max[i].max(position[i])),
+                        )
+                    },
+                );
+
+                FrameBounds {
+                    min,
+                    max,
+                    radius: ModelFrame::bounding_sphere_radius(&frame.vertices),
+                    stored_radius: frame.bounding_sphere_radius,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Issues with the sizes in the header, which would make the parser panic.
+fn header_issues(header: &ModelHeader) -> Vec<ModelIssue> {
+    let mut issues = vec![];
+
+    let minimum =
+        (FRAME_HEADER_SIZE as u64) + 4 * header.vertex_count as u64 + header.triangle_count as u64;
+    if (header.frame_size as u64) < minimum {
+        issues.push(ModelIssue::FrameSize {
+            frame_size: header.frame_size as usize,
+            minimum: minimum as usize,
+        });
+    }
+
+    let dimensions = TextureDimensions {
+        width: header.texture_width,
+        height: header.texture_height,
+    };
+    if dimensions.width == 0 || dimensions.height == 0 {
+        issues.push(ModelIssue::Texture {
+            width: dimensions.width as usize,
+            height: dimensions.height as usize,
+        });
+    }
+
+    issues
+}
+
+#[cfg(test)]
+#[cfg(feature = "conv")]
+mod tests {
+    use super::*;
+    use crate::asset::model::tests::model;
+
+    #[test]
+    fn validate_works() {
+        let model = model();
+
+        let report = model.validate();
+        assert_eq!(report.issues, []);
+        assert!(report.is_valid());
+        assert_eq!(report.frames[1].min, [0.0, 0.0, 1.0]);
+        assert_eq!(report.frames[1].max, [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn validate_finds_issues() {
+        let mut model = model();
+        model.triangles[0].points[1].vertex_index = 3;
+        model.triangles[0].points[2].u = 1.5;
+        model.sequences[0].frames.push(2);
+        model.frames[1].bounding_sphere_radius = 0.0;
+
+        let report = model.validate();
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.issues,
+            [
+                ModelIssue::VertexIndex {
+                    triangle: 0,
+                    vertex_index: 3,
+                    vertex_count: 3
+                },
+                ModelIssue::Uv {
+                    triangle: 0,
+                    uv: [1.5, 0.25]
+                },
+                ModelIssue::FrameIndex {
+                    sequence: 0,
+                    frame: 2,
+                    frame_count: 2
+                },
+                ModelIssue::BoundingSphere {
+                    frame: 1,
+                    stored: 0.0,
+                    computed: report.frames[1].radius
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_bytes_works() -> eyre::Result<()> {
+        let (bytes, _) = model().to_bytes()?;
+        assert!(Model::validate_bytes(&bytes).is_valid());
+
+        let report = Model::validate_bytes(&bytes[..bytes.len() - 1]);
+        assert!(matches!(
+            report.issues[..],
+            [ModelIssue::Section { name: "Frames", .. }]
+        ));
+
+        let mut broken = bytes.clone();
+        broken[20..24].copy_from_slice(&0u32.to_le_bytes());
+        let report = Model::validate_bytes(&broken);
+        assert!(matches!(
+            report.issues[0],
+            ModelIssue::FrameSize { frame_size: 0, .. }
+        ));
+
+        assert!(!Model::validate_bytes(&bytes[..10]).is_valid());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use super::{
+    Parser,
+    texture::{Texture, TextureSize},
+};
+use crate::utils::nom::*;
+
+/// Alternative texture of the player model (Jacob), used by the multiplayer
+/// colors.
+///
+/// Skins are stored as bare color indices, so their size has to come from the
+/// texture of the player model. They use the Jacob color map.
+pub struct Skin {
+    pub texture: Texture,
+}
+
+impl Parser for Skin {
+    type Context<'ctx> = TextureSize;
+
+    fn parser(size: Self::Context<'_>) -> impl Fn(Input) -> Result<Self> {
+        move |input| {
+            let (input, texture) = Texture::parser(size)(input)?;
+
+            Ok((input, Self { texture }))
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "conv")]
+mod tests {
+    use super::*;
+    use crate::{
+        asset::{color_map::ColorMap, model::Model},
+        utils::test::*,
+    };
+
+    #[test]
+    fn with_skin_works() -> eyre::Result<()> {
+        let model = Model {
+            texture: Texture {
+                colors: vec![vec![0; 2]; 2],
+            },
+            triangles: vec![],
+            sequences: vec![],
+            frames: vec![],
+            locators: vec![],
+            layout: None,
+        };
+        let size = TextureSize {
+            width: 2,
+            height: 2,
+        };
+
+        let (_, skin) = Skin::parser(size)(&[1, 2, 3, 4])?;
+        let skinned = model.with_skin(&skin);
+
+        assert_eq!(skinned.texture.colors, [[1, 2], [3, 4]]);
+        assert_eq!(model.texture.colors, [[0, 0], [0, 0]]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "uses Ashen ROM files"]
+    fn parse_rom_asset() -> eyre::Result<()> {
+        let (_, color_map) = ColorMap::parser(())(&deflated_file!("06.dat"))?;
+        let palette = &color_map.shades[15];
+        let (_, model) = Model::parser(())(&deflated_file!("14-deflated.dat"))?;
+        let size = TextureSize {
+            width: model.texture.width(),
+            height: model.texture.height(),
+        };
+
+        let skins = [
+            ("red", deflated_file!("48-deflated.dat")),
+            ("green", deflated_file!("49-deflated.dat")),
+            ("blue", deflated_file!("4A-deflated.dat")),
+            ("yellow", deflated_file!("4B-deflated.dat")),
+        ];
+
+        let parsed_path = PARSED_PATH;
+        skins.iter().try_for_each(|(name, data)| {
+            let (_, skin) = Skin::parser(size)(data)?;
+            let model = model.with_skin(&skin);
+
+            output_file(parsed_path.join(format!("skin/{name}.png")))
+                .and_then(|w| skin.texture.to_indexed_png(w, palette, None))?;
+
+            output_file(parsed_path.join(format!("skin/player-model-{name}.py")))
+                .and_then(|w| model.to_blender_script(w, palette, None))?;
+
+            Ok(())
+        })
+    }
+}
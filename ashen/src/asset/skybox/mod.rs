@@ -0,0 +1,345 @@
+mod projection;
+
+pub use projection::{CubeFace, SkyProjection};
+
+use super::{
+    Parser,
+    gamma_table::{Gamma, corrected_palette},
+    texture::{RgbaBuffer, Texture, TextureSize},
+};
+use crate::{asset::color_map::Color, utils::nom::*};
+
+const COLOR_COUNT: usize = 256;
+
+pub struct Skybox {
+    pub palette: Vec<Color>,
+    pub texture: Texture,
+}
+
+impl Parser for Skybox {
+    type Context<'ctx> = ();
+
+    fn parser((): Self::Context<'_>) -> impl Fn(Input) -> Result<Self> {
+        move |input| {
+            let (input, width) = number::le_u32(input)?;
+
+            let (input, height) = number::le_u32(input)?;
+
+            let (input, palette) = multi::count!(number::le_u16, 256)(input)?;
+            let palette: Vec<_> = palette.into_iter().map(Color::from_12_bit).collect();
+
+            let (_, texture) = Texture::parser(TextureSize {
+                width: width as usize,
+                height: height as usize,
+            })(input)?;
+
+            Ok((&[], Self { palette, texture }))
+        }
+    }
+}
+
+/// Replacement palette of a sky, used when the Ghost Vision Goggles are on.
+pub struct SkyboxGhostPalette {
+    pub palette: Vec<Color>,
+}
+
+impl Parser for SkyboxGhostPalette {
+    type Context<'ctx> = ();
+
+    fn parser((): Self::Context<'_>) -> impl Fn(Input) -> Result<Self> {
+        move |input| {
+            // Stored the same way as the palette of the sky itself.
+            let (input, palette) = multi::count!(number::le_u16, COLOR_COUNT)(input)?;
+            let palette = palette.into_iter().map(Color::from_12_bit).collect();
+
+            Ok((input, Self { palette }))
+        }
+    }
+}
+
+impl Skybox {
+    /// The same sky, with the colors it has when seen through the Ghost Vision
+    /// Goggles.
+    #[must_use]
+    pub fn with_ghost_palette(&self, ghost: &SkyboxGhostPalette) -> Self {
+        Self {
+            palette: ghost.palette.clone(),
+            texture: self.texture.clone(),
+        }
+    }
+
+    /// Resolves the sky through its palette into raw RGBA pixels.
+    ///
+    /// Texels that use the `transparent` index (if any) get an alpha of 0.
+    pub fn to_rgba(&self, transparent: Option<u8>, gamma: Option<Gamma>) -> RgbaBuffer {
+        RgbaBuffer::from_indices(
+            &self.texture.colors,
+            &corrected_palette(&self.palette, gamma),
+            transparent,
+        )
+    }
+
+    #[cfg(feature = "conv")]
+    pub fn to_png<W>(&self, mut writer: W, gamma: Option<Gamma>) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        use crate::{asset::texture::PaletteTexture, utils::format::PngFile};
+
+        let palette = corrected_palette(&self.palette, gamma);
+        writer.write_all(&self.texture.with_palette(&palette).to_png())
+    }
+
+    /// Writes the sky as a palettized PNG, keeping the original color indices
+    /// intact.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    #[cfg(feature = "conv")]
+    pub fn to_indexed_png<W>(&self, mut writer: W, gamma: Option<Gamma>) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        use crate::utils::format::IndexedPngFile;
+
+        let palette = corrected_palette(&self.palette, gamma);
+        writer.write_all(&self.texture.colors.to_indexed_png(&palette))
+    }
+
+    /// Reads a palettized PNG, taking the color indices and the palette
+    /// verbatim.
+    ///
+    /// Palettes with less than 256 colors are padded with black.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the PNG can't be decoded or isn't palettized.
+    #[cfg(feature = "conv")]
+    pub fn from_indexed_png<R>(reader: R) -> std::io::Result<Self>
+    where
+        R: std::io::Read,
+    {
+        let (texture, mut palette) = Texture::from_indexed_png(reader)?;
+        palette.resize(COLOR_COUNT, Color { r: 0, g: 0, b: 0 });
+
+        Ok(Self { palette, texture })
+    }
+
+    /// Reads any PNG, building a 12-bit palette for it and quantizing it to
+    /// that palette.
+    ///
+    /// Images with 256 or less distinct 12-bit colors are imported without any
+    /// loss besides rounding to 12-bit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the PNG can't be decoded.
+    #[cfg(feature = "conv")]
+    pub fn from_png<R>(reader: R) -> std::io::Result<Self>
+    where
+        R: std::io::Read,
+    {
+        use crate::{
+            asset::color_map::{ColorDistance, PaletteIndex, median_cut},
+            utils::format::read_png,
+        };
+
+        let colors = read_png(reader)?;
+
+        let mut palette = median_cut(colors.iter().flatten().copied(), COLOR_COUNT);
+        palette.resize(COLOR_COUNT, Color { r: 0, g: 0, b: 0 });
+
+        let index = PaletteIndex::new(&palette, ColorDistance::Rgb);
+        let texture = Texture {
+            colors: colors
+                .iter()
+                .map(|row| row.iter().map(|&color| index.nearest(color)).collect())
+                .collect(),
+        };
+
+        Ok(Self { palette, texture })
+    }
+
+    /// Encodes the sky in the same format the game stores it in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the palette doesn't have exactly 256 colors, or if
+    /// any of them doesn't fit in the 12-bit color space.
+    pub fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        use std::io::{Error, ErrorKind};
+
+        if self.palette.len() != COLOR_COUNT {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Palette has {} colors instead of {COLOR_COUNT}",
+                    self.palette.len()
+                ),
+            ));
+        }
+
+        let mut bytes = vec![];
+        bytes.extend((self.texture.width() as u32).to_le_bytes());
+        bytes.extend((self.texture.height() as u32).to_le_bytes());
+        for (i, color) in self.palette.iter().enumerate() {
+            let color = color.to_12_bit().ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Color #{:0>6X} at index {i} doesn't fit in 12-bit color space",
+                        color.to_u32()
+                    ),
+                )
+            })?;
+            bytes.extend(color.to_le_bytes());
+        }
+        bytes.extend(self.texture.colors.iter().flatten());
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "conv")]
+mod tests {
+    use std::cell::LazyCell;
+
+    use super::*;
+    use crate::utils::test::*;
+
+    #[test]
+    fn to_bytes_works() -> eyre::Result<()> {
+        let bytes = [
+            u32::to_le_bytes(3).as_slice(),
+            &u32::to_le_bytes(2),
+            &(0..COLOR_COUNT as u16)
+                .flat_map(|i| u16::to_le_bytes(i * 0x10))
+                .collect::<Vec<_>>(),
+            &[0, 1, 2, 255, 17, 17],
+        ]
+        .concat();
+
+        let (_, skybox) = Skybox::parser(())(&bytes)?;
+
+        assert_eq!(skybox.to_bytes()?, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_ghost_palette_works() -> eyre::Result<()> {
+        let bytes = (0..COLOR_COUNT as u16)
+            .flat_map(|i| u16::to_le_bytes(0xFFF - i))
+            .collect::<Vec<_>>();
+        let (_, ghost) = SkyboxGhostPalette::parser(())(&bytes)?;
+        let skybox = Skybox {
+            palette: vec![Color { r: 0, g: 0, b: 0 }; COLOR_COUNT],
+            texture: Texture {
+                colors: vec![vec![0, 1]],
+            },
+        };
+
+        let ghost_skybox = skybox.with_ghost_palette(&ghost);
+
+        assert_eq!(ghost_skybox.texture, skybox.texture);
+        assert_eq!(
+            ghost_skybox.to_rgba(None, None).pixel(1, 0),
+            [0xFF, 0xFF, 0xEE, 0xFF]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_png_works() -> eyre::Result<()> {
+        let skybox = Skybox {
+            palette: (0..COLOR_COUNT as u16)
+                .map(|i| Color::from_12_bit(i * 0x10 + 0xF))
+                .collect(),
+            texture: Texture {
+                colors: vec![vec![0, 1, 2], vec![255, 17, 17]],
+            },
+        };
+
+        let mut png = vec![];
+        skybox.to_png(&mut png, None)?;
+        let imported = Skybox::from_png(png.as_slice())?;
+
+        assert_eq!(imported.palette.len(), COLOR_COUNT);
+        assert_eq!(imported.to_rgba(None, None), skybox.to_rgba(None, None));
+
+        Ok(())
+    }
+
+    const SKYBOXES: LazyCell<Vec<(&str, Vec<u8>)>> = LazyCell::new(|| {
+        vec![
+            ("level1", deflated_file!("3C.dat")),
+            ("level2", deflated_file!("3D.dat")),
+            ("level3", deflated_file!("3E.dat")),
+            ("level4", deflated_file!("3F.dat")),
+            ("level5", deflated_file!("40.dat")),
+            ("level6", deflated_file!("41.dat")),
+        ]
+    });
+
+    #[test]
+    #[ignore = "uses Ashen ROM files"]
+    fn parse_rom_asset() -> eyre::Result<()> {
+        let ghost_palettes = [
+            deflated_file!("42.dat"),
+            deflated_file!("43.dat"),
+            deflated_file!("44.dat"),
+            deflated_file!("45.dat"),
+            deflated_file!("46.dat"),
+            deflated_file!("47.dat"),
+        ];
+
+        let skybox_path = PARSED_PATH.join("skybox");
+        Iterator::zip(SKYBOXES.iter(), ghost_palettes.iter()).try_for_each(
+            |((name, data), ghost_data)| {
+                let (_, skybox) = Skybox::parser(())(data)?;
+                let (_, ghost_palette) = SkyboxGhostPalette::parser(())(ghost_data)?;
+
+                output_file(skybox_path.join(format!("{name}.png")))
+                    .and_then(|w| skybox.to_png(w, None))?;
+
+                output_file(skybox_path.join(format!("{name}-ghost.png")))
+                    .and_then(|w| skybox.with_ghost_palette(&ghost_palette).to_png(w, None))?;
+
+                // `LevelData` lives in the executable, so the sky is projected
+                // with a single revolution.
+                let projection = SkyProjection::default();
+                let size = skybox.texture.width();
+                output_file(skybox_path.join(format!("{name}-panorama.png"))).and_then(|w| {
+                    skybox
+                        .to_equirectangular(size, size / 2, projection)
+                        .to_png(w, None)
+                })?;
+                for (face, cube_face) in Iterator::zip(
+                    CubeFace::ALL.iter(),
+                    skybox.to_cubemap(size / 4, projection),
+                ) {
+                    output_file(skybox_path.join(format!("{name}-cubemap-{}.png", face.suffix())))
+                        .and_then(|w| cube_face.to_png(w, None))?;
+                }
+
+                let mut png = vec![];
+                skybox.to_indexed_png(&mut png, None)?;
+                let decoded = Skybox::from_indexed_png(png.as_slice())?;
+                assert_eq!(decoded.palette, skybox.palette);
+                assert_eq!(decoded.texture, skybox.texture);
+
+                assert!(skybox.to_bytes()? == *data, "{name} is not byte-identical");
+
+                let mut png = vec![];
+                skybox.to_png(&mut png, None)?;
+                let imported = Skybox::from_png(png.as_slice())?;
+                assert_eq!(imported.to_rgba(None, None), skybox.to_rgba(None, None));
+
+                Ok(())
+            },
+        )
+    }
+}
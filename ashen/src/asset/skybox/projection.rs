@@ -0,0 +1,213 @@
+use std::f32::consts::{PI, TAU};
+
+use super::Skybox;
+use crate::asset::{level_data::LevelData, texture::Texture};
+
+/// How the sky texture is wrapped around the view sphere.
+///
+/// The texture is repeated around the horizon, starting at the `+Z` direction
+/// and going towards `+X`, while rows go from straight up to straight down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkyProjection {
+    /// How many times the texture wraps around the horizon.
+    pub horizontal_revolutions: f32,
+    /// How many times the texture fits between the top and the bottom of the
+    /// view sphere.
+    ///
+    /// Rows that fall outside of the texture repeat its first or last row.
+    pub vertical_revolutions: f32,
+    /// Vertical shift of the texture, in texels.
+    pub vertical_offset: i32,
+}
+
+impl Default for SkyProjection {
+    fn default() -> Self {
+        Self {
+            horizontal_revolutions: 1.0,
+            vertical_revolutions: 1.0,
+            vertical_offset: 0,
+        }
+    }
+}
+
+impl From<&LevelData> for SkyProjection {
+    fn from(level: &LevelData) -> Self {
+        Self {
+            horizontal_revolutions: level.sky_horizontal_texture_revolutions.to_num(),
+            vertical_revolutions: level.sky_vertical_texture_revolutions.to_num(),
+            vertical_offset: level.sky_vertical_offset,
+        }
+    }
+}
+
+/// A face of a cubemap, in the order used by OpenGL and most engines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    /// Direction going through a point of the face, with `s` and `t` in
+    /// `-1..=1` from the top-left corner.
+    fn direction(self, s: f32, t: f32) -> [f32; 3] {
+        match self {
+            CubeFace::PositiveX => [1.0, -t, -s],
+            CubeFace::NegativeX => [-1.0, -t, s],
+            CubeFace::PositiveY => [s, 1.0, t],
+            CubeFace::NegativeY => [s, -1.0, -t],
+            CubeFace::PositiveZ => [s, -t, 1.0],
+            CubeFace::NegativeZ => [-s, -t, -1.0],
+        }
+    }
+
+    /// Short name used by engines, such as `px` or `nz`.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            CubeFace::PositiveX => "px",
+            CubeFace::NegativeX => "nx",
+            CubeFace::PositiveY => "py",
+            CubeFace::NegativeY => "ny",
+            CubeFace::PositiveZ => "pz",
+            CubeFace::NegativeZ => "nz",
+        }
+    }
+}
+
+impl Skybox {
+    /// Projects the sky into an equirectangular panorama, the left edge
+    /// facing `+Z`.
+    ///
+    /// The panorama keeps the palette of the sky, so a gamma level is applied
+    /// when writing it (see [`Skybox::to_png`]).
+    #[must_use]
+    pub fn to_equirectangular(
+        &self,
+        width: usize,
+        height: usize,
+        projection: SkyProjection,
+    ) -> Skybox {
+        let colors = (0..height)
+            .map(|y| {
+                let polar = (y as f32 + 0.5) / height as f32 * PI;
+                (0..width)
+                    .map(|x| {
+                        let yaw = (x as f32 + 0.5) / width as f32 * TAU;
+                        self.sample(yaw, polar, projection)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Skybox {
+            palette: self.palette.clone(),
+            texture: Texture { colors },
+        }
+    }
+
+    /// Projects the sky into the 6 faces of a cubemap, in the order of
+    /// [`CubeFace::ALL`], with gamma applied when writing them like the
+    /// panorama.
+    pub fn to_cubemap(&self, size: usize, projection: SkyProjection) -> [Skybox; 6] {
+        CubeFace::ALL.map(|face| {
+            let coordinate = |i: usize| (i as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+
+            let colors = (0..size)
+                .map(|y| {
+                    (0..size)
+                        .map(|x| {
+                            let [dx, dy, dz] = face.direction(coordinate(x), coordinate(y));
+                            let yaw = f32::atan2(dx, dz).rem_euclid(TAU);
+                            let polar = f32::atan2(f32::hypot(dx, dz), dy);
+                            self.sample(yaw, polar, projection)
+                        })
+                        .collect()
+                })
+                .collect();
+
+            Skybox {
+                palette: self.palette.clone(),
+                texture: Texture { colors },
+            }
+        })
+    }
+
+    /// The color index seen when looking at `yaw` (`0..TAU` from `+Z` towards
+    /// `+X`) and `polar` (`0..=PI` from straight up) angles.
+    fn sample(&self, yaw: f32, polar: f32, projection: SkyProjection) -> u8 {
+        let (width, height) = (self.texture.width(), self.texture.height());
+
+        let u = yaw / TAU * projection.horizontal_revolutions * width as f32;
+        let v = polar / PI * projection.vertical_revolutions * height as f32
+            + projection.vertical_offset as f32;
+
+        let x = u.floor().rem_euclid(width as f32) as usize % width;
+        let y = v.floor().clamp(0.0, (height - 1) as f32) as usize;
+
+        self.texture.colors[y][x]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::color_map::Color;
+
+    fn skybox() -> Skybox {
+        Skybox {
+            palette: vec![Color { r: 0, g: 0, b: 0 }; 256],
+            texture: Texture {
+                colors: (0..4)
+                    .map(|y| (0..8).map(|x| y * 8 + x).collect())
+                    .collect(),
+            },
+        }
+    }
+
+    #[test]
+    fn to_equirectangular_works() {
+        let skybox = skybox();
+
+        let panorama = skybox.to_equirectangular(8, 4, SkyProjection::default());
+        assert_eq!(panorama.texture, skybox.texture);
+
+        let panorama = skybox.to_equirectangular(
+            8,
+            4,
+            SkyProjection {
+                horizontal_revolutions: 2.0,
+                vertical_revolutions: 1.0,
+                vertical_offset: 1,
+            },
+        );
+        assert_eq!(panorama.texture.colors[0], [9, 11, 13, 15, 9, 11, 13, 15]);
+        assert_eq!(panorama.texture.colors[3][0], 25);
+    }
+
+    #[test]
+    fn to_cubemap_works() {
+        let skybox = skybox();
+
+        let faces = skybox.to_cubemap(2, SkyProjection::default());
+
+        // Up and down only see the first and last rows.
+        assert!(faces[2].texture.colors.iter().flatten().all(|&i| i < 8));
+        assert!(faces[3].texture.colors.iter().flatten().all(|&i| i >= 24));
+        // Side faces see the middle rows, +Z is the start of the texture.
+        assert_eq!(faces[4].texture.colors[0], [15, 8]);
+        assert_eq!(faces[4].texture.colors[1], [23, 16]);
+    }
+}
@@ -0,0 +1,362 @@
+use super::{Texture, WorldTexture};
+
+/// Settings used when packing a [`TextureAtlas`].
+#[derive(Debug, Clone, Copy)]
+pub struct AtlasOptions {
+    /// Maximum width and height of a page.
+    ///
+    /// Textures that are bigger than this get a page of their own.
+    pub max_size: usize,
+    /// Space left around each texture, to avoid bleeding when sampling.
+    ///
+    /// It's filled with the texels on the edges of the texture, so filtering
+    /// near an edge doesn't pick up colors of the neighboring textures.
+    pub padding: usize,
+    /// Whether to pack all 4 mip levels instead of only the first one.
+    pub mips: bool,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        Self {
+            max_size: 1024,
+            padding: 0,
+            mips: false,
+        }
+    }
+}
+
+/// Location of a single image inside of an atlas page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// A world texture packed into an atlas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtlasEntry {
+    /// Index of the texture in the texture bank.
+    pub texture_id: usize,
+    pub mip: usize,
+    /// Regions of every animation frame (only one for static textures).
+    ///
+    /// Animation frames are textures of their own, so every frame is shared
+    /// with the entry of [`AtlasFrame::texture_id`].
+    pub frames: Vec<AtlasFrame>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasFrame {
+    pub texture_id: usize,
+    pub region: AtlasRegion,
+}
+
+/// All world textures packed into one or more pages.
+pub struct TextureAtlas {
+    pub pages: Vec<Texture>,
+    pub entries: Vec<AtlasEntry>,
+}
+
+impl TextureAtlas {
+    pub fn new(textures: &[WorldTexture], options: AtlasOptions) -> Self {
+        let mips = if options.mips { 0..4 } else { 0..1 };
+
+        // The first frame of every texture is the texture itself, so only
+        // those need to be packed.
+        let images = textures
+            .iter()
+            .enumerate()
+            .flat_map(|(id, texture)| {
+                mips.clone().map(move |mip| {
+                    let image = match texture {
+                        WorldTexture::Static(mips) => &mips[mip],
+                        WorldTexture::Animated(mips) => &mips[mip].frames[0],
+                    };
+                    ((id, mip), image)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let (pages, regions) = pack(&images, options);
+
+        let entries = images
+            .iter()
+            .map(|&((texture_id, mip), _)| {
+                let frames = match &textures[texture_id] {
+                    WorldTexture::Static(_) => vec![texture_id],
+                    WorldTexture::Animated(mips) => mips[mip].texture_ids.clone(),
+                };
+                let frames = frames
+                    .into_iter()
+                    .map(|texture_id| AtlasFrame {
+                        texture_id,
+                        region: regions[&(texture_id, mip)],
+                    })
+                    .collect();
+
+                AtlasEntry {
+                    texture_id,
+                    mip,
+                    frames,
+                }
+            })
+            .collect();
+
+        Self { pages, entries }
+    }
+
+    /// Writes the map of the atlas as JSON.
+    ///
+    /// Every entry contains the original size of the texture, as well as the
+    /// page coordinates and normalized UV rectangle (`[u_min, v_min, u_max,
+    /// v_max]`, with the origin on the top-left) of every animation frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    #[cfg(feature = "conv")]
+    pub fn to_json<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        use serde_json::json;
+
+        let json = json!({
+            "pages": self.pages.iter().map(|page| json!({
+                "width": page.width(),
+                "height": page.height(),
+            })).collect::<Vec<_>>(),
+            "textures": self.entries.iter().map(|entry| {
+                let AtlasRegion { width, height, .. } = entry.frames[0].region;
+
+                json!({
+                    "index": entry.texture_id,
+                    "mip": entry.mip,
+                    "width": width,
+                    "height": height,
+                    "frames": entry.frames.iter().map(|frame| {
+                        let region = frame.region;
+                        let page = &self.pages[region.page];
+                        let (page_width, page_height) =
+                            (page.width() as f32, page.height() as f32);
+
+                        json!({
+                            "texture_id": frame.texture_id,
+                            "page": region.page,
+                            "x": region.x,
+                            "y": region.y,
+                            "uv": [
+                                region.x as f32 / page_width,
+                                region.y as f32 / page_height,
+                                (region.x + region.width) as f32 / page_width,
+                                (region.y + region.height) as f32 / page_height,
+                            ],
+                        })
+                    }).collect::<Vec<_>>(),
+                })
+            }).collect::<Vec<_>>(),
+        });
+
+        serde_json::to_writer_pretty(&mut writer, &json)?;
+        Ok(())
+    }
+}
+
+/// Packs images into pages using rows ("shelves") of images sorted by height.
+fn pack<K>(
+    images: &[(K, &Texture)],
+    options: AtlasOptions,
+) -> (Vec<Texture>, std::collections::HashMap<K, AtlasRegion>)
+where
+    K: Copy + Eq + std::hash::Hash,
+{
+    struct Page {
+        width: usize,
+        height: usize,
+        cursor_x: usize,
+        cursor_y: usize,
+        shelf_height: usize,
+    }
+
+    let padding = options.padding;
+
+    let mut order = (0..images.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| std::cmp::Reverse((images[i].1.height(), images[i].1.width())));
+
+    let mut pages: Vec<Page> = vec![];
+    let mut regions = std::collections::HashMap::with_capacity(images.len());
+
+    for i in order {
+        let (key, image) = images[i];
+        let (width, height) = (image.width() + 2 * padding, image.height() + 2 * padding);
+
+        let fits = |page: &Page| {
+            if page.cursor_x + width <= page.width
+                && page.cursor_y + height.max(page.shelf_height) <= options.max_size
+            {
+                Some((page.cursor_x, page.cursor_y))
+            } else if page.cursor_y + page.shelf_height + height <= options.max_size
+                && width <= page.width
+            {
+                Some((0, page.cursor_y + page.shelf_height))
+            } else {
+                None
+            }
+        };
+
+        let (index, (x, y)) = pages
+            .iter()
+            .enumerate()
+            .find_map(|(index, page)| fits(page).map(|position| (index, position)))
+            .unwrap_or_else(|| {
+                pages.push(Page {
+                    width: options.max_size.max(width),
+                    height: 0,
+                    cursor_x: 0,
+                    cursor_y: 0,
+                    shelf_height: 0,
+                });
+                (pages.len() - 1, (0, 0))
+            });
+
+        let page = &mut pages[index];
+        if y != page.cursor_y {
+            page.cursor_y = y;
+            page.shelf_height = 0;
+        }
+        page.cursor_x = x + width;
+        page.shelf_height = page.shelf_height.max(height);
+        page.height = page.height.max(y + height);
+
+        regions.insert(
+            key,
+            AtlasRegion {
+                page: index,
+                x: x + padding,
+                y: y + padding,
+                width: image.width(),
+                height: image.height(),
+            },
+        );
+    }
+
+    let mut textures = pages
+        .iter()
+        .map(|page| Texture {
+            colors: vec![vec![0; page.width]; page.height],
+        })
+        .collect::<Vec<_>>();
+    for (key, image) in images {
+        let region = regions[key];
+        if image.width() == 0 || image.height() == 0 {
+            continue;
+        }
+
+        // Texels of the padding repeat the closest texel of the image.
+        let page = &mut textures[region.page];
+        for y in region.y - padding..region.y + region.height + padding {
+            let row = &image.colors[(y.saturating_sub(region.y)).min(region.height - 1)];
+            for x in region.x - padding..region.x + region.width + padding {
+                page.colors[y][x] = row[(x.saturating_sub(region.x)).min(region.width - 1)];
+            }
+        }
+    }
+
+    (textures, regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(size: usize, color: u8) -> Texture {
+        Texture {
+            colors: vec![vec![color; size]; size],
+        }
+    }
+
+    #[test]
+    fn pack_works() {
+        let images = [
+            (0, &texture(4, 1)),
+            (1, &texture(8, 2)),
+            (2, &texture(4, 3)),
+            (3, &texture(16, 4)),
+        ];
+
+        let (pages, regions) = pack(
+            &images,
+            AtlasOptions {
+                max_size: 16,
+                padding: 0,
+                mips: false,
+            },
+        );
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(regions[&3].page, 0);
+        assert_eq!(regions[&1].page, 1);
+        assert_eq!((regions[&1].x, regions[&1].y), (0, 0));
+        assert_eq!((regions[&0].x, regions[&0].y), (8, 0));
+        assert_eq!((regions[&2].x, regions[&2].y), (12, 0));
+        assert_eq!((pages[1].width(), pages[1].height()), (16, 8));
+        assert_eq!(pages[1].colors[0][8..], [1, 1, 1, 1, 3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn pack_extends_edges_into_padding() {
+        let image = Texture {
+            colors: vec![vec![1, 2], vec![3, 4]],
+        };
+
+        let (pages, regions) = pack(
+            &[(0, &image)],
+            AtlasOptions {
+                max_size: 16,
+                padding: 1,
+                mips: false,
+            },
+        );
+
+        assert_eq!((regions[&0].x, regions[&0].y), (1, 1));
+        assert_eq!(
+            pages[0]
+                .colors
+                .iter()
+                .map(|row| row[..4].to_vec())
+                .collect::<Vec<_>>(),
+            vec![
+                vec![1, 1, 2, 2],
+                vec![1, 1, 2, 2],
+                vec![3, 3, 4, 4],
+                vec![3, 3, 4, 4],
+            ]
+        );
+    }
+
+    #[test]
+    fn new_shares_animation_frames() {
+        let textures = [
+            WorldTexture::Static(std::array::from_fn(|_| texture(2, 1))),
+            WorldTexture::Animated(std::array::from_fn(|_| super::super::AnimatedTexture {
+                frames: vec![texture(2, 2), texture(2, 3)],
+                texture_ids: vec![1, 2],
+            })),
+            WorldTexture::Animated(std::array::from_fn(|_| super::super::AnimatedTexture {
+                frames: vec![texture(2, 3), texture(2, 2)],
+                texture_ids: vec![2, 1],
+            })),
+        ];
+
+        let atlas = TextureAtlas::new(&textures, AtlasOptions::default());
+
+        assert_eq!(atlas.pages.len(), 1);
+        assert_eq!(atlas.entries.len(), 3);
+        assert_eq!(atlas.entries[1].frames[1], atlas.entries[2].frames[0]);
+        assert_eq!(atlas.entries[2].frames[1].texture_id, 1);
+    }
+}
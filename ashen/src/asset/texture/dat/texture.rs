@@ -9,7 +9,7 @@ use crate::{
 };
 
 // TODO(nenikitov): Move this to a separate public module later
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Texture {
     pub colors: Vec<Vec<u8>>,
 }
@@ -41,6 +41,17 @@ impl Texture {
     pub fn height(&self) -> usize {
         self.colors.len()
     }
+
+    /// Copies `other` into this texture with its top-left corner at `x`, `y`.
+    ///
+    /// Parts of `other` that don't fit are cropped.
+    pub fn blit(&mut self, other: &Texture, x: usize, y: usize) {
+        for (dst, src) in Iterator::zip(self.colors.iter_mut().skip(y), &other.colors) {
+            for (dst, src) in Iterator::zip(dst.iter_mut().skip(x), src) {
+                *dst = *src;
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -0,0 +1,147 @@
+use super::Texture;
+use crate::asset::color_map::{Color, ColorDistance, PaletteIndex};
+
+/// How texels are combined when downsampling a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipFilter {
+    /// Averages the colors of every texel covered by the smaller texel and
+    /// picks the closest color of the palette.
+    Average,
+    /// Keeps the top-left texel of every block, without looking at colors.
+    Nearest,
+}
+
+impl Texture {
+    /// Builds the 4 mip levels that world textures store (full, 1/2, 1/4 and
+    /// 1/8 resolution), using this texture as the first one.
+    ///
+    /// Every level is downsampled from the full resolution texture, so the
+    /// quantization error doesn't build up. Dimensions should be multiples of
+    /// 8, like the ones of the original textures. Otherwise, levels are at
+    /// least 1 texel wide, and the texels past the last full block are
+    /// averaged into it.
+    pub fn generate_mips(&self, palette: &[Color; 256], filter: MipFilter) -> [Texture; 4] {
+        let index = PaletteIndex::new(palette, ColorDistance::Rgb);
+
+        std::array::from_fn(|level| match level {
+            0 => self.clone(),
+            _ => self.downsample(1 << level, &index, filter),
+        })
+    }
+
+    fn downsample(&self, factor: usize, index: &PaletteIndex, filter: MipFilter) -> Texture {
+        let palette = index.palette();
+        let width = (self.width() / factor).max(1);
+        let height = (self.height() / factor).max(1);
+
+        // Texels of the smaller texel, the last ones take the rest of the
+        // texture.
+        let block = |i: usize, size: usize, count: usize| {
+            let end = if i + 1 == count {
+                size
+            } else {
+                (i + 1) * factor
+            };
+            i * factor..end
+        };
+
+        let colors = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| match filter {
+                        MipFilter::Nearest => self.colors[y * factor][x * factor],
+                        MipFilter::Average => {
+                            let rows = block(y, self.height(), height);
+                            let columns = block(x, self.width(), width);
+                            let count = rows.len() * columns.len();
+                            let (r, g, b) = self.colors[rows]
+                                .iter()
+                                .flat_map(|row| &row[columns.clone()])
+                                .fold((0, 0, 0), |(r, g, b), &i| {
+                                    let c = palette[i as usize];
+                                    (r + c.r as usize, g + c.g as usize, b + c.b as usize)
+                                });
+                            let average = Color {
+                                r: ((r + count / 2) / count) as u8,
+                                g: ((g + count / 2) / count) as u8,
+                                b: ((b + count / 2) / count) as u8,
+                            };
+
+                            index.nearest(average)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Texture { colors }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PALETTE: [Color; 256] = {
+        let mut palette = [Color { r: 0, g: 0, b: 0 }; 256];
+        palette[1] = Color {
+            r: 0xFF,
+            g: 0xFF,
+            b: 0xFF,
+        };
+        palette[2] = Color {
+            r: 0x88,
+            g: 0x88,
+            b: 0x88,
+        };
+        palette
+    };
+
+    #[test]
+    fn generate_mips_average_works() {
+        let texture = Texture {
+            colors: (0..8)
+                .map(|y| (0..8).map(|x| ((x + y) % 2) as u8).collect())
+                .collect(),
+        };
+
+        let mips = texture.generate_mips(&PALETTE, MipFilter::Average);
+
+        assert_eq!(mips[0], texture);
+        assert_eq!(mips[1].colors, vec![vec![2; 4]; 4]);
+        assert_eq!(mips[3].colors, vec![vec![2]]);
+    }
+
+    #[test]
+    fn generate_mips_nearest_works() {
+        let texture = Texture {
+            colors: (0..8)
+                .map(|y| (0..8).map(|x| ((x + y) % 2) as u8).collect())
+                .collect(),
+        };
+
+        let mips = texture.generate_mips(&PALETTE, MipFilter::Nearest);
+
+        assert_eq!(mips[2].colors, vec![vec![0; 2]; 2]);
+    }
+
+    #[test]
+    fn generate_mips_small_works() {
+        // Smaller than the last factor, and not a multiple of the others.
+        let texture = Texture {
+            colors: vec![vec![1, 1, 0], vec![1, 1, 0], vec![0, 0, 0]],
+        };
+
+        for filter in [MipFilter::Average, MipFilter::Nearest] {
+            let mips = texture.generate_mips(&PALETTE, filter);
+            assert_eq!(mips[2].colors.len(), 1);
+            assert_eq!(mips[3].colors[0].len(), 1);
+        }
+
+        // The last column and row are averaged with the first block, so there
+        // are 4 white texels out of 9.
+        let mips = texture.generate_mips(&PALETTE, MipFilter::Average);
+        assert_eq!(mips[1].colors, vec![vec![2]]);
+        assert_eq!(mips[3].colors, vec![vec![2]]);
+    }
+}
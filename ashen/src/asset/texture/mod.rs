@@ -1,16 +1,41 @@
+mod atlas;
 mod dat;
+mod mip;
+mod remap;
+mod rgba;
 
+pub use atlas::{AtlasEntry, AtlasFrame, AtlasOptions, AtlasRegion, TextureAtlas};
 use dat::{offset::TextureOffset, texture::MippedTexture};
 pub use dat::{
     size::TextureSize,
     texture::{PaletteTexture, Texture},
 };
+pub use mip::MipFilter;
+pub use remap::rank_palettes;
+pub use rgba::RgbaBuffer;
 
-use super::Parser;
+use super::{
+    Parser,
+    gamma_table::{Gamma, corrected_palette},
+};
 use crate::utils::{compression::decompress, nom::*};
 
 pub struct AnimatedTexture {
     pub frames: Vec<Texture>,
+    /// Index of the texture each frame comes from, in the order of the
+    /// `next_animation_texture_id` chain.
+    pub texture_ids: Vec<usize>,
+}
+
+/// How the frames are arranged when exporting an [`AnimatedTexture`] as a
+/// sprite sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpriteSheetLayout {
+    /// All frames in a single row.
+    Horizontal,
+    /// Frames are laid out left to right, top to bottom, wrapping after
+    /// `columns` frames.
+    Grid { columns: usize },
 }
 
 // TODO(Unavailable): Implement `Index` and `IntoIterator`.
@@ -51,24 +76,30 @@ impl Parser for Vec<WorldTexture> {
 
             let textures = textures
                 .iter()
-                .cloned()
-                .map(|(texture, offset)| {
+                .enumerate()
+                .map(|(id, (texture, offset))| {
                     if offset.animation_frames == 0 {
-                        return WorldTexture::Static(texture.mips);
+                        return WorldTexture::Static(texture.mips.clone());
                     }
 
                     let mut mips: [_; 4] = std::array::from_fn(|_| {
                         Vec::with_capacity(offset.animation_frames as usize)
                     });
+                    let mut texture_ids = Vec::with_capacity(offset.animation_frames as usize);
 
-                    (0..offset.animation_frames).fold((texture, offset), |(texture, offset), _| {
-                        for (dst, src) in Iterator::zip(mips.iter_mut(), texture.mips) {
+                    (0..offset.animation_frames).fold(id, |id, _| {
+                        let (texture, offset) = &textures[id];
+                        for (dst, src) in Iterator::zip(mips.iter_mut(), texture.mips.clone()) {
                             dst.push(src);
                         }
-                        textures[offset.next_animation_texture_id as usize].clone()
+                        texture_ids.push(id);
+                        offset.next_animation_texture_id as usize
                     });
 
-                    WorldTexture::Animated(mips.map(|frames| AnimatedTexture { frames }))
+                    WorldTexture::Animated(mips.map(|frames| AnimatedTexture {
+                        frames,
+                        texture_ids: texture_ids.clone(),
+                    }))
                 })
                 .collect::<Vec<_>>();
 
@@ -78,11 +109,28 @@ impl Parser for Vec<WorldTexture> {
 }
 
 impl Texture {
+    /// Resolves the color indices into raw RGBA pixels.
+    ///
+    /// Texels that use the `transparent` index (if any) get an alpha of 0.
+    pub fn to_rgba(
+        &self,
+        palette: &[super::color_map::Color; 256],
+        transparent: Option<u8>,
+        gamma: Option<Gamma>,
+    ) -> RgbaBuffer {
+        RgbaBuffer::from_indices(
+            &self.colors,
+            &corrected_palette(palette, gamma),
+            transparent,
+        )
+    }
+
     #[cfg(feature = "conv")]
     pub fn to_png<W>(
         &self,
         mut writer: W,
         palette: &[super::color_map::Color; 256],
+        gamma: Option<Gamma>,
     ) -> std::io::Result<()>
     where
         W: std::io::Write,
@@ -91,16 +139,88 @@ impl Texture {
 
         use crate::utils::format::PngFile;
 
-        writer.write_all(&self.colors.with_palette(&*palette).to_png())
+        let palette = corrected_palette(palette, gamma);
+        writer.write_all(&self.colors.with_palette(&palette).to_png())
+    }
+
+    /// Writes the texture as a palettized PNG, keeping the original color
+    /// indices intact.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    #[cfg(feature = "conv")]
+    pub fn to_indexed_png<W>(
+        &self,
+        mut writer: W,
+        palette: &[super::color_map::Color; 256],
+        gamma: Option<Gamma>,
+    ) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        use crate::utils::format::IndexedPngFile;
+
+        writer.write_all(
+            &self
+                .colors
+                .to_indexed_png(&corrected_palette(palette, gamma)),
+        )
+    }
+
+    /// Writes the texture as an 8-bit PCX, keeping the original color indices
+    /// intact.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    #[cfg(feature = "conv")]
+    pub fn to_pcx<W>(
+        &self,
+        mut writer: W,
+        palette: &[super::color_map::Color; 256],
+        gamma: Option<Gamma>,
+    ) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        use crate::utils::format::PcxFile;
+
+        writer.write_all(&self.colors.to_pcx(&corrected_palette(palette, gamma)))
+    }
+
+    /// Reads a palettized PNG, taking the color indices verbatim.
+    ///
+    /// The colors of the `PLTE` chunk are returned alongside the texture.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the PNG can't be decoded, isn't palettized, or
+    /// doesn't have a `PLTE` chunk.
+    #[cfg(feature = "conv")]
+    pub fn from_indexed_png<R>(reader: R) -> std::io::Result<(Self, Vec<super::color_map::Color>)>
+    where
+        R: std::io::Read,
+    {
+        use crate::utils::format::read_indexed_png;
+
+        let (colors, palette) = read_indexed_png(reader)?;
+
+        Ok((Self { colors }, palette))
     }
 }
 
 impl AnimatedTexture {
+    // How fast the game cycles through animation frames is still unknown, 10
+    // FPS is a guess that looks about right.
+    const FRAME_DELAY_MS: u16 = 100;
+
     #[cfg(feature = "conv")]
     pub fn to_gif<W>(
         &self,
         mut writer: W,
         palette: &[super::color_map::Color; 256],
+        gamma: Option<Gamma>,
     ) -> std::io::Result<()>
     where
         W: std::io::Write,
@@ -109,14 +229,125 @@ impl AnimatedTexture {
 
         use crate::utils::format::GifFile;
 
+        let palette = corrected_palette(palette, gamma);
         let bytes = self
             .frames
             .iter()
-            .map(|texture| texture.with_palette(palette))
+            .map(|texture| texture.with_palette(&palette))
             .collect::<Vec<_>>()
             .to_gif();
         writer.write_all(&bytes)
     }
+
+    /// Writes the frames as a looping palettized APNG.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are no frames, or if writing fails.
+    #[cfg(feature = "conv")]
+    pub fn to_apng<W>(
+        &self,
+        mut writer: W,
+        palette: &[super::color_map::Color; 256],
+        gamma: Option<Gamma>,
+    ) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        use crate::utils::format::ApngFile;
+
+        if self.frames.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Animated texture has no frames",
+            ));
+        }
+
+        let bytes = self
+            .frames
+            .iter()
+            .map(|texture| texture.colors.as_slice())
+            .collect::<Vec<_>>()
+            .to_apng(&corrected_palette(palette, gamma), Self::FRAME_DELAY_MS);
+        writer.write_all(&bytes)
+    }
+
+    /// Writes all frames into a single PNG, along with a JSON manifest
+    /// describing where each frame is located.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there isn't a texture ID for every frame, or if
+    /// writing fails.
+    #[cfg(feature = "conv")]
+    pub fn to_sprite_sheet<W, M>(
+        &self,
+        writer: W,
+        mut manifest: M,
+        palette: &[super::color_map::Color; 256],
+        layout: SpriteSheetLayout,
+        gamma: Option<Gamma>,
+    ) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+        M: std::io::Write,
+    {
+        if self.texture_ids.len() != self.frames.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Animated texture has {} frames but {} texture IDs",
+                    self.frames.len(),
+                    self.texture_ids.len()
+                ),
+            ));
+        }
+
+        let frame_width = self.frames.iter().map(Texture::width).max().unwrap_or(0);
+        let frame_height = self.frames.iter().map(Texture::height).max().unwrap_or(0);
+        let columns = match layout {
+            SpriteSheetLayout::Horizontal => self.frames.len(),
+            SpriteSheetLayout::Grid { columns } => columns.min(self.frames.len()),
+        }
+        .max(1);
+        let rows = self.frames.len().div_ceil(columns);
+
+        let mut sheet = Texture {
+            colors: vec![vec![0; columns * frame_width]; rows * frame_height],
+        };
+        let frames = self
+            .frames
+            .iter()
+            .zip(&self.texture_ids)
+            .enumerate()
+            .map(|(i, (frame, texture_id))| {
+                let x = i % columns * frame_width;
+                let y = i / columns * frame_height;
+                sheet.blit(frame, x, y);
+
+                serde_json::json!({
+                    "texture_id": texture_id,
+                    "x": x,
+                    "y": y,
+                    "width": frame.width(),
+                    "height": frame.height(),
+                    "duration_ms": Self::FRAME_DELAY_MS,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        sheet.to_png(writer, palette, gamma)?;
+
+        let manifest_json = serde_json::json!({
+            "width": columns * frame_width,
+            "height": rows * frame_height,
+            "columns": columns,
+            "rows": rows,
+            "frames": frames,
+        });
+        serde_json::to_writer_pretty(&mut manifest, &manifest_json)?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -125,12 +356,223 @@ mod tests {
     use std::cell::LazyCell;
 
     use super::*;
-    use crate::{asset::color_map::ColorMap, utils::test::*};
+    use crate::{
+        asset::color_map::{Color, ColorMap},
+        utils::test::*,
+    };
 
     const COLOR_MAP: LazyCell<Vec<u8>> = LazyCell::new(|| deflated_file!("4F.dat"));
     const TEXTURE_INFO: LazyCell<Vec<u8>> = LazyCell::new(|| deflated_file!("93.dat"));
     const TEXTURE: LazyCell<Vec<u8>> = LazyCell::new(|| deflated_file!("95.dat"));
 
+    #[test]
+    fn indexed_png_round_trip_works() -> eyre::Result<()> {
+        let palette = std::array::from_fn(|i| Color::from_12_bit(i as u16 * 0x10));
+        let texture = Texture {
+            colors: vec![vec![0, 1, 2], vec![255, 17, 17]],
+        };
+
+        let mut png = vec![];
+        texture.to_indexed_png(&mut png, &palette, None)?;
+        let (decoded, decoded_palette) = Texture::from_indexed_png(png.as_slice())?;
+
+        assert_eq!(decoded, texture);
+        assert_eq!(decoded_palette, palette);
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_pcx_works() -> eyre::Result<()> {
+        let palette = std::array::from_fn(|i| Color::from_12_bit(i as u16 * 0x10));
+        let texture = Texture {
+            colors: vec![vec![0, 1, 2], vec![255, 17, 17]],
+        };
+
+        let mut pcx = vec![];
+        texture.to_pcx(&mut pcx, &palette, None)?;
+
+        assert_eq!(pcx.len(), 128 + 9 + 1 + 768);
+        assert_eq!(pcx[..4], [0x0A, 5, 1, 8]);
+        // Maximum X and Y, and bytes per line.
+        assert_eq!(pcx[8..12], [2, 0, 1, 0]);
+        assert_eq!(pcx[66..68], [4, 0]);
+        assert_eq!(pcx[128..137], [0, 1, 2, 0, 0xC1, 0xFF, 0xC2, 0x11, 0]);
+        assert_eq!(pcx[137], 0x0C);
+        assert_eq!(
+            pcx[138 + 3 * 255..],
+            [palette[255].r, palette[255].g, palette[255].b]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn sprite_sheet_works() -> eyre::Result<()> {
+        let palette = [Color { r: 0, g: 0, b: 0 }; 256];
+        let animated = AnimatedTexture {
+            frames: (1..=3)
+                .map(|i| Texture {
+                    colors: vec![vec![i; 2]; 2],
+                })
+                .collect(),
+            texture_ids: vec![4, 7, 5],
+        };
+
+        let mut png = vec![];
+        let mut manifest = vec![];
+        animated.to_sprite_sheet(
+            &mut png,
+            &mut manifest,
+            &palette,
+            SpriteSheetLayout::Grid { columns: 2 },
+            None,
+        )?;
+
+        let manifest: serde_json::Value = serde_json::from_slice(&manifest)?;
+        assert_eq!(manifest["width"], 4);
+        assert_eq!(manifest["height"], 4);
+        assert_eq!(manifest["frames"][1]["texture_id"], 7);
+        assert_eq!(manifest["frames"][1]["x"], 2);
+        assert_eq!(manifest["frames"][2]["x"], 0);
+        assert_eq!(manifest["frames"][2]["y"], 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sprite_sheet_checks_texture_ids() {
+        let palette = [Color { r: 0, g: 0, b: 0 }; 256];
+        let animated = AnimatedTexture {
+            frames: vec![
+                Texture {
+                    colors: vec![vec![0]]
+                };
+                2
+            ],
+            texture_ids: vec![4],
+        };
+
+        assert!(
+            animated
+                .to_sprite_sheet(
+                    vec![],
+                    vec![],
+                    &palette,
+                    SpriteSheetLayout::Horizontal,
+                    None
+                )
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn to_apng_checks_frames() {
+        let palette = [Color { r: 0, g: 0, b: 0 }; 256];
+        let animated = AnimatedTexture {
+            frames: vec![],
+            texture_ids: vec![],
+        };
+
+        assert!(animated.to_apng(vec![], &palette, None).is_err());
+    }
+
+    /// How close generated mips are to the ones of the ROM for every static
+    /// texture, as its index, the ratio of texels that match, and the mean RGB
+    /// distance between texels.
+    fn mip_metrics(
+        textures: &[WorldTexture],
+        palette: &[Color; 256],
+        filter: MipFilter,
+    ) -> Vec<(usize, f64, f64)> {
+        textures
+            .iter()
+            .enumerate()
+            .filter_map(|(i, texture)| match texture {
+                WorldTexture::Static(mips) => Some((i, mips)),
+                WorldTexture::Animated(_) => None,
+            })
+            .map(|(i, mips)| {
+                let (mut matching, mut total, mut error) = (0, 0, 0.0);
+                let generated = mips[0].generate_mips(palette, filter);
+
+                for (generated, original) in Iterator::zip(generated.iter(), mips).skip(1) {
+                    for (g, o) in Iterator::zip(
+                        generated.colors.iter().flatten(),
+                        original.colors.iter().flatten(),
+                    ) {
+                        let (g, o) = (palette[*g as usize], palette[*o as usize]);
+                        let distance = ((g.r as f64 - o.r as f64).powi(2)
+                            + (g.g as f64 - o.g as f64).powi(2)
+                            + (g.b as f64 - o.b as f64).powi(2))
+                        .sqrt();
+
+                        matching += usize::from(g == o);
+                        total += 1;
+                        error += distance;
+                    }
+                }
+
+                (i, matching as f64 / total as f64, error / total as f64)
+            })
+            .collect()
+    }
+
+    #[test]
+    #[ignore = "uses Ashen ROM files"]
+    fn generate_mips_matches_rom() -> eyre::Result<()> {
+        // Under a tenth of the range of a channel.
+        const MAX_ERROR: f64 = 24.0;
+
+        let (color_map, texture_info, texture) = (COLOR_MAP, TEXTURE_INFO, TEXTURE);
+
+        let (_, color_map) = ColorMap::parser(())(&color_map)?;
+        let palette = &color_map.shades[15];
+
+        let (_, offsets) = Vec::<TextureOffset>::parser(())(&texture_info)?;
+        let (_, textures) = Vec::<WorldTexture>::parser(&offsets)(&texture)?;
+
+        let average = mip_metrics(&textures, palette, MipFilter::Average);
+        let nearest = mip_metrics(&textures, palette, MipFilter::Nearest);
+
+        let report = Iterator::zip(average.iter(), &nearest)
+            .map(
+                |((i, matching, error), (_, nearest_matching, nearest_error))| {
+                    format!(
+                        "{i:0>3X}: {:.1}% matching and {error:.1} error \
+                     ({:.1}% and {nearest_error:.1} with the nearest texel)",
+                        matching * 100.0,
+                        nearest_matching * 100.0
+                    )
+                },
+            )
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mean = |metrics: &[(usize, f64, f64)]| {
+            let count = metrics.len() as f64;
+            metrics
+                .iter()
+                .fold((0.0, 0.0), |(matching, error), (_, m, e)| {
+                    (matching + m / count, error + e / count)
+                })
+        };
+        let (average_matching, average_error) = mean(&average);
+        let (nearest_matching, nearest_error) = mean(&nearest);
+
+        assert!(
+            average.iter().all(|(_, _, error)| *error < MAX_ERROR),
+            "Generated mips are too far from the game:\n{report}"
+        );
+        // Averaging is what generated mips default to, so it has to be the
+        // closest to the game.
+        assert!(
+            average_error < nearest_error && average_matching >= nearest_matching,
+            "Averaging is further from the game than the nearest texel:\n{report}"
+        );
+
+        Ok(())
+    }
+
     #[test]
     #[ignore = "uses Ashen ROM files"]
     fn parse_rom_asset() -> eyre::Result<()> {
@@ -142,6 +584,13 @@ mod tests {
 
         let output_dir = PARSED_PATH.join("texture");
 
+        let atlas = TextureAtlas::new(&textures, AtlasOptions::default());
+        atlas.pages.iter().enumerate().try_for_each(|(i, page)| {
+            output_file(output_dir.join(format!("atlas/{i}.png")))
+                .and_then(|w| page.to_indexed_png(w, palette, None))
+        })?;
+        output_file(output_dir.join("atlas/atlas.json")).and_then(|w| atlas.to_json(w))?;
+
         textures
             .iter()
             .enumerate()
@@ -149,7 +598,7 @@ mod tests {
                 WorldTexture::Static(r#static) => {
                     r#static.iter().enumerate().try_for_each(|(j, texture)| {
                         output_file(output_dir.join(format!("{i:0>3X}-mip-{j}.png")))
-                            .and_then(|w| texture.to_png(w, palette))
+                            .and_then(|w| texture.to_png(w, palette, None))
                     })
                 }
                 WorldTexture::Animated(animated) => {
@@ -158,7 +607,23 @@ mod tests {
                         .enumerate()
                         .try_for_each(|(j, animated_texture)| {
                             output_file(output_dir.join(format!("{i:0>3X}-mip-{j}.gif")))
-                                .and_then(|w| animated_texture.to_gif(w, palette))
+                                .and_then(|w| animated_texture.to_gif(w, palette, None))?;
+
+                            output_file(output_dir.join(format!("{i:0>3X}-mip-{j}.apng")))
+                                .and_then(|w| animated_texture.to_apng(w, palette, None))?;
+
+                            output_file(output_dir.join(format!("{i:0>3X}-mip-{j}-sheet.png")))
+                                .and_then(|w| {
+                                    animated_texture.to_sprite_sheet(
+                                        w,
+                                        output_file(
+                                            output_dir.join(format!("{i:0>3X}-mip-{j}-sheet.json")),
+                                        )?,
+                                        palette,
+                                        SpriteSheetLayout::Horizontal,
+                                        None,
+                                    )
+                                })
                         })
                 }
             })?;
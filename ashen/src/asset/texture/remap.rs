@@ -0,0 +1,214 @@
+use std::io::{self, Error, ErrorKind};
+
+use super::Texture;
+use crate::asset::color_map::{Color, ColorDistance, PaletteIndex};
+
+impl Texture {
+    /// Converts the texture from the `from` palette to the palette of `to`,
+    /// picking the closest color for every index.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the texture uses an index that `from` doesn't have.
+    pub fn remap(&self, from: &[Color], to: &PaletteIndex) -> io::Result<Texture> {
+        self.check_palette(from)?;
+
+        let lookup = from
+            .iter()
+            .map(|&color| to.nearest(color))
+            .collect::<Vec<_>>();
+
+        Ok(Texture {
+            colors: self
+                .colors
+                .iter()
+                .map(|row| row.iter().map(|&i| lookup[i as usize]).collect())
+                .collect(),
+        })
+    }
+
+    /// Mean distance between the colors of neighboring texels when the
+    /// texture is displayed with `palette`, relative to the standard
+    /// deviation of their luminance.
+    ///
+    /// Textures are mostly made of smooth gradients, so the wrong palette
+    /// usually shows up as noise and a higher value. Dividing by the contrast
+    /// keeps palettes that flatten the texture from looking smooth, and ones
+    /// that brighten it from looking noisy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the texture uses an index that `palette` doesn't
+    /// have.
+    pub fn roughness(&self, palette: &[Color], distance: ColorDistance) -> io::Result<f32> {
+        self.check_palette(palette)?;
+
+        let horizontal = self
+            .colors
+            .iter()
+            .flat_map(|row| row.windows(2).map(|pair| (pair[0], pair[1])));
+        let vertical = self
+            .colors
+            .windows(2)
+            .flat_map(|rows| Iterator::zip(rows[0].iter().copied(), rows[1].iter().copied()));
+
+        let (sum, count) = horizontal
+            .chain(vertical)
+            .fold((0.0, 0), |(sum, count), (a, b)| {
+                let distance = distance.between(palette[a as usize], palette[b as usize]);
+                (sum + distance, count + 1)
+            });
+
+        let contrast = self.luminance_deviation(palette);
+        Ok(if count == 0 || contrast == 0.0 {
+            0.0
+        } else {
+            sum / count as f32 / contrast
+        })
+    }
+
+    /// Standard deviation of the luminance of the texels, between 0 and 1.
+    fn luminance_deviation(&self, palette: &[Color]) -> f32 {
+        let luminances = self
+            .colors
+            .iter()
+            .flatten()
+            .map(|&i| {
+                let color = palette[i as usize];
+                (0.2126 * color.r as f32 + 0.7152 * color.g as f32 + 0.0722 * color.b as f32)
+                    / 255.0
+            })
+            .collect::<Vec<_>>();
+        if luminances.is_empty() {
+            return 0.0;
+        }
+
+        let count = luminances.len() as f32;
+        let mean = luminances.iter().sum::<f32>() / count;
+        let variance = luminances
+            .iter()
+            .map(|luminance| (luminance - mean).powi(2))
+            .sum::<f32>()
+            / count;
+
+        variance.sqrt()
+    }
+
+    fn check_palette(&self, palette: &[Color]) -> io::Result<()> {
+        match self.colors.iter().flatten().max() {
+            Some(&index) if index as usize >= palette.len() => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Texture uses color {index}, but the palette only has {} colors",
+                    palette.len()
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Ranks `palettes` by how plausible `texture` looks with them, from the most
+/// to the least plausible, along with their [`Texture::roughness`].
+///
+/// This is meant to help find the color map of assets that don't specify it.
+///
+/// # Errors
+///
+/// Returns an error if the texture uses an index that one of the palettes
+/// doesn't have.
+pub fn rank_palettes<'p, K, I>(
+    texture: &Texture,
+    palettes: I,
+    distance: ColorDistance,
+) -> io::Result<Vec<(K, f32)>>
+where
+    I: IntoIterator<Item = (K, &'p [Color])>,
+{
+    let mut ranking = palettes
+        .into_iter()
+        .map(|(key, palette)| Ok((key, texture.roughness(palette, distance)?)))
+        .collect::<io::Result<Vec<_>>>()?;
+    ranking.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    Ok(ranking)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Color = Color { r: 0, g: 0, b: 0 };
+    const GRAY: Color = Color {
+        r: 0x88,
+        g: 0x88,
+        b: 0x88,
+    };
+    const WHITE: Color = Color {
+        r: 0xFF,
+        g: 0xFF,
+        b: 0xFF,
+    };
+
+    #[test]
+    fn remap_works() -> eyre::Result<()> {
+        let texture = Texture {
+            colors: vec![vec![0, 1], vec![2, 1]],
+        };
+        let index = PaletteIndex::new(&[WHITE, GRAY, BLACK], ColorDistance::Rgb);
+
+        let remapped = texture.remap(&[BLACK, WHITE, GRAY], &index)?;
+        assert_eq!(remapped.colors, vec![vec![2, 0], vec![1, 0]]);
+
+        assert!(texture.remap(&[BLACK, WHITE], &index).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rank_palettes_works() -> eyre::Result<()> {
+        // A gradient from black to white.
+        let texture = Texture {
+            colors: vec![vec![0, 1, 2]; 3],
+        };
+        let smooth = [BLACK, GRAY, WHITE];
+        let noisy = [BLACK, WHITE, BLACK];
+
+        let ranking = rank_palettes(
+            &texture,
+            [("noisy", noisy.as_slice()), ("smooth", smooth.as_slice())],
+            ColorDistance::Lab,
+        )?;
+
+        assert_eq!(ranking[0].0, "smooth");
+        assert_eq!(ranking[1].0, "noisy");
+        assert!(ranking[0].1 < ranking[1].1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn roughness_is_relative_to_contrast() -> eyre::Result<()> {
+        let texture = Texture {
+            colors: vec![vec![0, 1, 2]; 3],
+        };
+        let dark = [
+            BLACK,
+            Color { r: 8, g: 8, b: 8 },
+            Color {
+                r: 16,
+                g: 16,
+                b: 16,
+            },
+        ];
+
+        // The same gradient, with less contrast, is as smooth.
+        let smooth = texture.roughness(&[BLACK, GRAY, WHITE], ColorDistance::Rgb)?;
+        let dark = texture.roughness(&dark, ColorDistance::Rgb)?;
+        assert!((smooth - dark).abs() < 0.05 * smooth);
+
+        assert!(texture.roughness(&[BLACK], ColorDistance::Rgb).is_err());
+
+        Ok(())
+    }
+}
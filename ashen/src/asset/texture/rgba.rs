@@ -0,0 +1,95 @@
+use crate::asset::color_map::Color;
+
+/// Raw 8-bit RGBA pixels, stored row by row from the top-left corner.
+///
+/// Unlike the PNG and GIF conversions, this doesn't need the `conv` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl RgbaBuffer {
+    const CHANNELS: usize = 4;
+
+    /// Resolves a 2D array of color indices through `palette`.
+    ///
+    /// Texels that use the `transparent` index (if any) get an alpha of 0.
+    pub fn from_indices<Inner>(
+        indices: &[Inner],
+        palette: &[Color],
+        transparent: Option<u8>,
+    ) -> Self
+    where
+        Inner: AsRef<[u8]>,
+    {
+        let width = indices.first().map_or(0, |row| row.as_ref().len());
+        let height = indices.len();
+
+        let data = indices
+            .iter()
+            .flat_map(AsRef::as_ref)
+            .flat_map(|&i| {
+                let Color { r, g, b } = palette[i as usize];
+                let a = if transparent == Some(i) { 0 } else { 255 };
+                [r, g, b, a]
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Builds an opaque buffer from a 2D array of colors.
+    pub fn from_colors<Inner>(colors: &[Inner]) -> Self
+    where
+        Inner: AsRef<[Color]>,
+    {
+        let width = colors.first().map_or(0, |row| row.as_ref().len());
+        let height = colors.len();
+
+        let data = colors
+            .iter()
+            .flat_map(AsRef::as_ref)
+            .flat_map(|&Color { r, g, b }| [r, g, b, 255])
+            .collect();
+
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// The `[r, g, b, a]` value of a pixel.
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let offset = (y * self.width + x) * Self::CHANNELS;
+        let pixel = &self.data[offset..][..Self::CHANNELS];
+        [pixel[0], pixel[1], pixel[2], pixel[3]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_indices_works() {
+        let palette = [
+            Color { r: 1, g: 2, b: 3 },
+            Color { r: 4, g: 5, b: 6 },
+            Color { r: 7, g: 8, b: 9 },
+        ];
+
+        let buffer = RgbaBuffer::from_indices(&[[0, 1], [2, 0]], &palette, Some(2));
+
+        assert_eq!((buffer.width, buffer.height), (2, 2));
+        assert_eq!(buffer.pixel(1, 0), [4, 5, 6, 255]);
+        assert_eq!(buffer.pixel(0, 1), [7, 8, 9, 0]);
+        assert_eq!(buffer.data.len(), 16);
+    }
+}
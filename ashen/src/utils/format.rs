@@ -1,7 +1,7 @@
 use std::ops::Deref;
 
 use image::{
-    Frame, ImageEncoder, RgbaImage,
+    Delay, Frame, ImageEncoder, RgbaImage,
     codecs::{
         gif::{GifEncoder, Repeat},
         png::PngEncoder,
@@ -13,7 +13,7 @@ use crate::asset::{
     color_map::Color,
     model::Model,
     sound::sample::{AudioBuffer, AudioSamplePoint},
-    texture::PaletteTexture,
+    texture::{PaletteTexture, RgbaBuffer},
 };
 
 pub trait PngFile {
@@ -54,6 +54,184 @@ where
     }
 }
 
+pub trait IndexedPngFile {
+    /// Encodes the color indices as-is, with `palette` stored in the `PLTE`
+    /// chunk.
+    fn to_indexed_png(&self, palette: &[Color]) -> Vec<u8>;
+}
+
+// impl for any 2D array like data structure.
+impl<Outer: ?Sized, Inner> IndexedPngFile for Outer
+where
+    Outer: Deref<Target = [Inner]>,
+    Inner: AsRef<[u8]>,
+{
+    fn to_indexed_png(&self, palette: &[Color]) -> Vec<u8> {
+        let width = self[0].as_ref().len() as u32;
+        let height = self.len() as u32;
+
+        let mut data = vec![];
+        let mut encoder = png::Encoder::new(&mut data, width, height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(
+            palette
+                .iter()
+                .flat_map(|color| [color.r, color.g, color.b])
+                .collect::<Vec<_>>(),
+        );
+
+        encoder
+            .write_header()
+            .and_then(|mut w| {
+                w.write_image_data(&self.iter().flat_map(AsRef::as_ref).copied().collect_vec())
+            })
+            .expect("Generated image data must be valid");
+
+        data
+    }
+}
+
+pub trait PcxFile {
+    /// Encodes the color indices as-is into an 8-bit PCX, with `palette`
+    /// stored after the image data.
+    fn to_pcx(&self, palette: &[Color]) -> Vec<u8>;
+}
+
+// impl for any 2D array like data structure.
+impl<Outer: ?Sized, Inner> PcxFile for Outer
+where
+    Outer: Deref<Target = [Inner]>,
+    Inner: AsRef<[u8]>,
+{
+    fn to_pcx(&self, palette: &[Color]) -> Vec<u8> {
+        let width = self[0].as_ref().len();
+        let height = self.len();
+        // Lines have an even number of bytes.
+        let line_size = width.next_multiple_of(2);
+
+        let mut data = vec![
+            0x0A, // Manufacturer
+            5,    // Version
+            1,    // Run-length encoding
+            8,    // Bits per pixel
+        ];
+        for value in [0, 0, width - 1, height - 1, 72, 72] {
+            data.extend((value as u16).to_le_bytes());
+        }
+        data.extend([0; 48]);
+        data.extend([0, 1]);
+        for value in [line_size, 1, 0, 0] {
+            data.extend((value as u16).to_le_bytes());
+        }
+        data.extend([0; 54]);
+
+        for line in self.iter() {
+            let mut line = line.as_ref().to_vec();
+            line.resize(line_size, 0);
+            for run in line.chunk_by(|a, b| a == b) {
+                let (color, mut run) = (run[0], run.len());
+                // Runs are at most 63 bytes long, and bytes that look like a
+                // run header have to be stored as a run.
+                while run > 0 {
+                    let count = run.min(63);
+                    if count > 1 || color >= 0xC0 {
+                        data.push(0xC0 | count as u8);
+                    }
+                    data.push(color);
+                    run -= count;
+                }
+            }
+        }
+
+        data.push(0x0C);
+        data.extend(
+            palette
+                .iter()
+                .flat_map(|color| [color.r, color.g, color.b])
+                .chain(std::iter::repeat(0))
+                .take(3 * 256),
+        );
+
+        data
+    }
+}
+
+/// Decodes a palettized PNG, returning the color indices verbatim (without
+/// going through the palette) along with the colors from the `PLTE` chunk.
+pub fn read_indexed_png<R>(reader: R) -> std::io::Result<(Vec<Vec<u8>>, Vec<Color>)>
+where
+    R: std::io::Read,
+{
+    use std::io::{Error, ErrorKind};
+
+    let mut reader = png::Decoder::new(reader).read_info()?;
+
+    let info = reader.info();
+    if info.color_type != png::ColorType::Indexed {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("PNG is not palettized ({:?})", info.color_type),
+        ));
+    }
+    let width = info.width as usize;
+    let palette = info
+        .palette
+        .as_deref()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "PNG is missing a `PLTE` chunk"))?
+        .as_chunks()
+        .0
+        .iter()
+        .map(|&[r, g, b]| Color { r, g, b })
+        .collect();
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer)?;
+
+    // Indices of palettes with less than 256 colors may be packed into less
+    // than 8 bits.
+    let bits = frame.bit_depth as usize;
+    let indices = buffer[..frame.buffer_size()]
+        .chunks_exact(frame.line_size)
+        .map(|line| {
+            line.iter()
+                .flat_map(|byte| {
+                    (0..8 / bits)
+                        .rev()
+                        .map(move |i| (byte >> (i * bits)) & ((1u16 << bits) - 1) as u8)
+                })
+                .take(width)
+                .collect()
+        })
+        .collect();
+
+    Ok((indices, palette))
+}
+
+/// Decodes any PNG into rows of colors, dropping the alpha channel.
+pub fn read_png<R>(mut reader: R) -> std::io::Result<Vec<Vec<Color>>>
+where
+    R: std::io::Read,
+{
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+
+    let image = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+        .to_rgb8();
+
+    Ok(image
+        .rows()
+        .map(|row| {
+            row.map(|pixel| {
+                let [r, g, b] = pixel.0;
+                Color { r, g, b }
+            })
+            .collect()
+        })
+        .collect())
+}
+
 pub trait GifFile {
     fn to_gif(&self) -> Vec<u8>;
 }
@@ -101,6 +279,128 @@ where
     }
 }
 
+pub trait ApngFile {
+    /// Encodes palettized frames into a looping APNG, keeping the color
+    /// indices intact.
+    ///
+    /// An APNG needs at least one frame, so nothing is encoded without frames.
+    fn to_apng(&self, palette: &[Color], frame_delay_ms: u16) -> Vec<u8>;
+}
+
+impl<Outer: ?Sized, Inner1, Inner2> ApngFile for Outer
+where
+    Outer: Deref<Target = [Inner1]>,
+    Inner1: Deref<Target = [Inner2]>,
+    Inner2: AsRef<[u8]>,
+{
+    fn to_apng(&self, palette: &[Color], frame_delay_ms: u16) -> Vec<u8> {
+        if self.is_empty() {
+            return vec![];
+        }
+
+        let width = self[0][0].as_ref().len() as u32;
+        let height = self[0].len() as u32;
+
+        let mut data = vec![];
+        let mut encoder = png::Encoder::new(&mut data, width, height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(
+            palette
+                .iter()
+                .flat_map(|color| [color.r, color.g, color.b])
+                .collect::<Vec<_>>(),
+        );
+        encoder
+            .set_animated(self.len() as u32, 0)
+            .and_then(|()| encoder.set_frame_delay(frame_delay_ms, 1000))
+            .expect("Generated animation must be valid");
+
+        let mut writer = encoder
+            .write_header()
+            .expect("Generated image data must be valid");
+        for frame in self.iter() {
+            writer
+                .write_image_data(&frame.iter().flat_map(AsRef::as_ref).copied().collect_vec())
+                .expect("Generated image frames must be valid");
+        }
+        writer
+            .finish()
+            .expect("Generated image frames must be valid");
+
+        data
+    }
+}
+
+pub trait RgbaAnimationFile {
+    /// Encodes frames into a looping GIF, where pixels with an alpha of 0 are
+    /// transparent.
+    fn to_gif(&self, frame_delay_ms: u16) -> Vec<u8>;
+
+    /// Encodes frames into a looping APNG, or nothing without frames.
+    fn to_apng(&self, frame_delay_ms: u16) -> Vec<u8>;
+}
+
+impl RgbaAnimationFile for [RgbaBuffer] {
+    fn to_gif(&self, frame_delay_ms: u16) -> Vec<u8> {
+        let mut data = vec![];
+        let mut encoder = GifEncoder::new_with_speed(&mut data, 10);
+
+        encoder
+            .encode_frames(self.iter().map(|frame| {
+                Frame::from_parts(
+                    RgbaImage::from_vec(
+                        frame.width as u32,
+                        frame.height as u32,
+                        frame.data.clone(),
+                    )
+                    .expect("Generated image data must be valid"),
+                    0,
+                    0,
+                    Delay::from_numer_denom_ms(frame_delay_ms as u32, 1),
+                )
+            }))
+            .expect("Generated image frames must be valid");
+
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .expect("Generated image frames must loop");
+
+        drop(encoder);
+
+        data
+    }
+
+    fn to_apng(&self, frame_delay_ms: u16) -> Vec<u8> {
+        if self.is_empty() {
+            return vec![];
+        }
+
+        let mut data = vec![];
+        let mut encoder = png::Encoder::new(&mut data, self[0].width as u32, self[0].height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(self.len() as u32, 0)
+            .and_then(|()| encoder.set_frame_delay(frame_delay_ms, 1000))
+            .expect("Generated animation must be valid");
+
+        let mut writer = encoder
+            .write_header()
+            .expect("Generated image data must be valid");
+        for frame in self {
+            writer
+                .write_image_data(&frame.data)
+                .expect("Generated image frames must be valid");
+        }
+        writer
+            .finish()
+            .expect("Generated image frames must be valid");
+
+        data
+    }
+}
+
 pub trait WaveFile<S: AudioSamplePoint> {
     fn to_wave(&self) -> Vec<u8>
     where
@@ -189,9 +489,10 @@ impl ModelPythonFile for Model {
         };
 
         macro_rules! display_sequence {
-            ($s:expr) => {
+            ($i:expr, $s:expr) => {
                 format!(
-                    r#"{{ "frames": [{}] }}"#,
+                    r#"{{ "name": {:?}, "frames": [{}] }}"#,
+                    self.sequence_name($i),
                     $s.frames.iter().map(u32::to_string).join(", ")
                 )
             };
@@ -265,7 +566,7 @@ for i, f in enumerate(frames):
 actions = []
 mesh.shape_keys.animation_data_create()
 for i, s in enumerate(sequences):
-    actions.append(bpy.data.actions.new(f"Action {{i}}"))
+    actions.append(bpy.data.actions.new(s["name"]))
     actions[i].use_fake_user = True
     actions[i].frame_end = len(s["frames"])
     actions[i].use_frame_range = True
@@ -303,9 +604,8 @@ object.select_set(True)
                 .iter()
                 .map(|t| format!("    {}", display_triangle!(t)))
                 .join(",\n"),
-            self.sequences
-                .iter()
-                .map(|s| format!("    {}", display_sequence!(s)))
+            (0..self.sequences.len())
+                .map(|i| format!("    {}", display_sequence!(i, self.sequences[i])))
                 .join(",\n")
         )
     }